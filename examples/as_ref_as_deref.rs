@@ -1,3 +1,5 @@
+#![allow(dead_code, unused_variables)]
+
 struct Test {
     name: String,
     age: i32,
@@ -13,7 +13,7 @@ async fn main() -> Result<()> {
         Ok(c) => c,
         Err(_e) => todo!(),
     };
-    let b = c
+    let output = c
         .get_profile("todo")
         .unwrap()
         .diff(ExtraArgs {
@@ -22,5 +22,6 @@ async fn main() -> Result<()> {
             query: vec![],
         })
         .await?;
+    println!("{}", output);
     Ok(())
 }
@@ -1,52 +1,178 @@
+use anyhow::Context;
 use clap::Parser;
+use console::{Style, Term};
 use dialoguer::{theme::ColorfulTheme, Input, MultiSelect};
+use futures::{stream, StreamExt};
+use reqwest::Client;
+use std::collections::BTreeMap;
 use std::io::Write;
+use std::process::ExitCode;
 use xdiff::{
-    cli::{Action, Args, RunArgs},
-    highlight_text, DiffConfig, DiffProfile, ExtraArgs, RequestProfile, ResponseProfile,
+    cli::{Action, Args, DiffRunArgs, ExitStatus, Layout, LintArgs, OutputFormat, ParseArgs},
+    highlight_text, html_report, process_error_output, split_tags, DiffConfig, DiffProfile,
+    ExtraArgs, LoadOptions, ProfileFilter, ProfileReport, Report, RequestProfile, ResponseProfile,
+    Summary, TemplateVars,
 };
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    let args = Args::<DiffRunArgs>::parse();
 
     let result = match args.action {
-        Action::Run(args) => run(args).await,
-        Action::Parse => parse().await,
+        Action::Run(args) => run(*args).await,
+        Action::Parse(args) => parse(args).await.map(|_| ExitStatus::Success),
+        Action::Schema => schema().map(|_| ExitStatus::Success),
+        Action::Lint(args) => lint(args).await,
     };
 
-    match result {
-        Ok(_) => (),
-        Err(err) => println!("{:?}", err),
-    }
-
-    Ok(())
+    process_error_output(result).into()
 }
 
-async fn run(args: RunArgs) -> anyhow::Result<()> {
-    let config_file = args.config.unwrap_or_else(|| "./xdiff.yaml".to_string());
-    let config = DiffConfig::load_yaml(&config_file).await?;
+async fn run(args: DiffRunArgs) -> anyhow::Result<ExitStatus> {
+    let filter = ProfileFilter::from(&args.run);
+    let options = LoadOptions {
+        vars: TemplateVars::load(&args.run.vars, &args.run.env_file).await?,
+        env1: args.run.env1.clone(),
+        env2: args.env2.clone(),
+        ..Default::default()
+    };
+    let config_files = match args.run.config.is_empty() {
+        true => vec!["./xdiff.yaml".to_string()],
+        false => args.run.config,
+    };
+    let config = DiffConfig::load_yaml_files(&config_files, &options).await?;
     config.validate()?;
-    let profile = config.get_profile(&args.profile).ok_or_else(|| {
-        anyhow::anyhow!(
-            "Profile {} is not found in config file {}",
-            args.profile,
-            config_file
+    let profiles = config.select_profiles(&filter).with_context(|| {
+        format!(
+            "failed to select profiles in config file {}",
+            config_files.join(", ")
         )
     })?;
 
-    let extra_args: ExtraArgs = args.extra_params.into();
+    if args.output == OutputFormat::Patch {
+        let paired = |profile: &DiffProfile| profile.res.arrays.iter().any(|r| !r.pairs_by_index());
+        if let Some((name, _)) = profiles.iter().find(|(_, profile)| paired(profile)) {
+            return Err(anyhow::anyhow!(
+                "profile {} pairs array elements by key or regardless of order, \
+                 which a patch can't express, use --output json instead",
+                name
+            ));
+        }
+    }
 
-    let output = profile.diff(extra_args).await?;
+    let extra_args: ExtraArgs = args.run.extra_params.into();
+    let show_names = profiles.len() > 1;
 
     let mut stdout = std::io::stdout();
+    let mut summary = Summary::default();
+    let mut results = vec![];
+
+    let client = Client::new();
+    // `buffered` keeps the profile order, so output stays sorted by name
+    let mut outcomes = stream::iter(profiles)
+        .map(|(name, profile)| {
+            let (client, extra_args) = (&client, &extra_args);
+            async move { (name, profile.compare(client, extra_args).await) }
+        })
+        .buffered(args.run.concurrency as usize);
+
+    while let Some((name, outcome)) = outcomes.next().await {
+        let result = match outcome {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    Style::new().red().apply_to(format!("{}: {:?}", name, e))
+                );
+                summary.push_error(name, &e);
+                continue;
+            }
+        };
+        summary.push_result(name, &result, &args.fail_on);
+
+        if args.output == OutputFormat::Text {
+            if show_names {
+                writeln!(
+                    stdout,
+                    "{}",
+                    Style::new().bold().apply_to(format!("== {} ==", name))
+                )?;
+            }
+            match args.layout {
+                Layout::Unified => write!(stdout, "{}", result.to_text()?)?,
+                Layout::SideBySide => {
+                    let width = Term::stdout()
+                        .size_checked()
+                        .map_or(160, |(_, w)| w as usize);
+                    write!(stdout, "{}", result.to_side_by_side(width)?)?
+                }
+            }
+        }
+        results.push((name, result));
+    }
 
-    write!(stdout, "{}", output)?;
+    match args.output {
+        OutputFormat::Text if show_names => write!(stdout, "\n{}", summary.to_text()?)?,
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            let report = Report {
+                profiles: results
+                    .iter()
+                    .map(|(name, result)| ProfileReport::new(name, result))
+                    .collect(),
+                summary: summary.clone(),
+            };
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&report)?)?;
+        }
+        OutputFormat::Patch => {
+            let patches: BTreeMap<_, _> = results
+                .iter()
+                .map(|(name, result)| (name, result.to_patch()))
+                .collect();
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&patches)?)?;
+        }
+    }
+
+    if let Some(path) = &args.report {
+        let results: Vec<_> = results
+            .iter()
+            .map(|(name, result)| (*name, result))
+            .collect();
+        tokio::fs::write(path, html_report(&results, &summary)?).await?;
+    }
 
+    Ok(summary.exit_status())
+}
+
+async fn lint(args: LintArgs) -> anyhow::Result<ExitStatus> {
+    let options = LoadOptions {
+        vars: TemplateVars::load(&args.vars, &args.env_file).await?,
+        ..Default::default()
+    };
+    let config_files = match args.config.is_empty() {
+        true => vec!["./xdiff.yaml".to_string()],
+        false => args.config,
+    };
+    let report = DiffConfig::lint_files(&config_files, &options).await;
+    write!(std::io::stdout(), "{}", report)?;
+
+    Ok(match report.has_errors() {
+        true => ExitStatus::Error,
+        false => ExitStatus::Success,
+    })
+}
+
+fn schema() -> anyhow::Result<()> {
+    let schema = schemars::schema_for!(DiffConfig);
+    writeln!(
+        std::io::stdout(),
+        "{}",
+        serde_json::to_string_pretty(&schema)?
+    )?;
     Ok(())
 }
 
-async fn parse() -> anyhow::Result<()> {
+async fn parse(args: ParseArgs) -> anyhow::Result<()> {
     let theme = &ColorfulTheme::default();
     let url1: String = Input::with_theme(theme).with_prompt("url1").interact()?;
     let url2: String = Input::with_theme(theme).with_prompt("url2").interact()?;
@@ -65,18 +191,31 @@ async fn parse() -> anyhow::Result<()> {
         .items(&headers)
         .interact()?;
 
-    let skip_headers = chosen.iter().map(|i| headers[*i].to_string()).collect();
+    let skip_headers = chosen
+        .iter()
+        .map(|i| headers[*i].parse())
+        .collect::<anyhow::Result<_>>()?;
+    let tags: String = Input::with_theme(theme)
+        .with_prompt("Tags (comma separated)")
+        .allow_empty(true)
+        .interact()?;
+
     let res = ResponseProfile::new(skip_headers, vec![]);
-    let profile = DiffProfile::new(req1, req2, res);
+    let mut profile = DiffProfile::new(req1, req2, res);
+    profile.tags = split_tags(&tags);
     let config = DiffConfig::new(vec![(name, profile)].into_iter().collect());
-    let result = serde_yaml::to_string(&config)?;
+    let result = args.format.to_string(&config)?;
 
     let stdout = std::io::stdout();
     let mut stdout = stdout.lock();
     // if atty::is(atty::Stream::Stdout) {
-    // write!(stdout, "{}", highlight_text(&result, "yaml", None)?)?;
+    // write!(stdout, "{}", highlight_text(&result, args.format.extension(), None)?)?;
     // } else {
-    write!(stdout, "{}", highlight_text(&result, "yaml", None)?)?;
+    write!(
+        stdout,
+        "{}",
+        highlight_text(&result, args.format.extension(), None)?
+    )?;
     // }
 
     Ok(())
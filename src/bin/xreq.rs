@@ -1,51 +1,135 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Input;
+use futures::{stream, StreamExt};
+use reqwest::Client;
+use std::collections::BTreeMap;
 use std::fmt::Write as _;
 use std::io::Write as _;
-use xdiff::cli::{Args, RunArgs};
+use std::process::ExitCode;
+use xdiff::cli::{Args, ExitStatus, LintArgs, ParseArgs, ReqRunArgs};
 use xdiff::{
-    get_body_text, get_header_text, get_status_text, highlight_text, process_error_output,
-    LoadConfig, RequestConfig, RequestProfile,
+    extract_values, get_body_text, get_header_text, get_status_text, highlight_text,
+    process_error_output, split_tags, ConfigFormat, ExtraArgs, LoadConfig, LoadOptions,
+    ProfileFilter, RequestConfig, RequestProfile, TemplateVars,
 };
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    let args = Args::<ReqRunArgs>::parse();
 
     let result = match args.action {
-        xdiff::cli::Action::Run(args) => run(args).await,
-        xdiff::cli::Action::Parse => parse().await,
+        xdiff::cli::Action::Run(args) => run(*args).await.map(|_| ExitStatus::Success),
+        xdiff::cli::Action::Parse(args) => parse(args).await.map(|_| ExitStatus::Success),
+        xdiff::cli::Action::Schema => schema().map(|_| ExitStatus::Success),
+        xdiff::cli::Action::Lint(args) => lint(args).await,
     };
 
-    process_error_output(result);
-
-    Ok(())
+    process_error_output(result).into()
 }
 
-async fn run(args: RunArgs) -> Result<()> {
-    let config_file = args
-        .config
-        .unwrap_or_else(|| "./fixtures/xreq_test.yaml".to_string());
-    let config = RequestConfig::load_yaml(&config_file).await?;
-    let profile = config.get_profile(&args.profile).ok_or_else(|| {
-        anyhow::anyhow!(
-            "Profile {} not found in config file {}",
-            args.profile,
-            config_file
+async fn run(args: ReqRunArgs) -> Result<()> {
+    let filter = ProfileFilter::from(&args.run);
+    let options = LoadOptions {
+        vars: TemplateVars::load(&args.run.vars, &args.run.env_file).await?,
+        env1: args.run.env1.clone(),
+        sequence: args.sequence,
+        ..Default::default()
+    };
+    let config_files = match args.run.config.is_empty() {
+        true => vec!["./fixtures/xreq_test.yaml".to_string()],
+        false => args.run.config,
+    };
+    let config = RequestConfig::load_yaml_files(&config_files, &options).await?;
+    let profiles = match args.sequence {
+        true => config.select_sequence(&filter),
+        false => config.select_profiles(&filter),
+    }
+    .with_context(|| {
+        format!(
+            "failed to select profiles in config file {}",
+            config_files.join(", ")
         )
     })?;
 
-    let extra_args = args.extra_params.into();
+    let extra_args = args.run.extra_params.into();
+    let show_names = profiles.len() > 1;
+    let client = Client::new();
+
+    if args.sequence {
+        let mut values = BTreeMap::new();
+        for (name, profile) in profiles {
+            let (output, extracted) = send(&client, profile, &extra_args, &values)
+                .await
+                .with_context(|| format!("profile {} failed", name))?;
+            print_output(name, &output, show_names)?;
+            values.extend(extracted);
+        }
+        return Ok(());
+    }
+
+    let values = BTreeMap::new();
+    let mut outputs = stream::iter(profiles)
+        .map(|(name, profile)| {
+            let (client, extra_args, values) = (&client, &extra_args, &values);
+            async move { (name, send(client, profile, extra_args, values).await) }
+        })
+        .buffered(args.run.concurrency as usize);
+
+    while let Some((name, output)) = outputs.next().await {
+        print_output(name, &output?.0, show_names)?;
+    }
+
+    Ok(())
+}
+
+fn print_output(name: &str, output: &str, show_names: bool) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    if show_names {
+        writeln!(stdout, "== {} ==", name)?;
+    }
+    write!(stdout, "{}", output)?;
+    Ok(())
+}
+
+async fn lint(args: LintArgs) -> Result<ExitStatus> {
+    let options = LoadOptions {
+        vars: TemplateVars::load(&args.vars, &args.env_file).await?,
+        // profiles may be meant for --sequence, so what they extract is known
+        sequence: true,
+        ..Default::default()
+    };
+    let config_files = match args.config.is_empty() {
+        true => vec!["./fixtures/xreq_test.yaml".to_string()],
+        false => args.config,
+    };
+    let report = RequestConfig::lint_files(&config_files, &options).await;
+    write!(std::io::stdout(), "{}", report)?;
+
+    Ok(match report.has_errors() {
+        true => ExitStatus::Error,
+        false => ExitStatus::Success,
+    })
+}
 
-    let url = profile.get_url(&extra_args)?;
+/// Send a profile, returning its printable output and the values it extracts.
+async fn send(
+    client: &Client,
+    profile: &RequestProfile,
+    extra_args: &ExtraArgs,
+    values: &BTreeMap<String, String>,
+) -> Result<(String, BTreeMap<String, String>)> {
+    let profile = profile.with_vars(values)?;
+    let url = profile.get_url(extra_args)?;
 
-    let res = profile.send(&extra_args).await?.into_inner();
+    let res = profile.send_with(client, extra_args).await?.into_inner();
 
     let status = get_status_text(&res)?;
     let headers = get_header_text(&res, &[])?;
+    let (status_code, header_map) = (res.status(), res.headers().clone());
     let body = get_body_text(res, &[]).await?;
+    let extracted = extract_values(&profile.extract, status_code, &header_map, &body)?;
 
     let mut output = String::new();
 
@@ -58,34 +142,55 @@ async fn run(args: RunArgs) -> Result<()> {
     )?;
 
     write!(&mut output, "{}", highlight_text(&body, "json", None)?)?;
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
-    write!(stdout, "{}", output)?;
+    Ok((output, extracted))
+}
 
+fn schema() -> Result<()> {
+    let schema = schemars::schema_for!(RequestConfig);
+    writeln!(
+        std::io::stdout(),
+        "{}",
+        serde_json::to_string_pretty(&schema)?
+    )?;
     Ok(())
 }
 
-async fn parse() -> Result<()> {
+async fn parse(args: ParseArgs) -> Result<()> {
     let theme = ColorfulTheme::default();
     let url: String = Input::with_theme(&theme)
         .with_prompt("Url")
         .interact_text()?;
-    let profile: RequestProfile = url.parse()?;
+    let mut profile: RequestProfile = url.parse()?;
 
     let name: String = Input::with_theme(&theme)
         .with_prompt("Profile")
         .interact_text()?;
 
+    let tags: String = Input::with_theme(&theme)
+        .with_prompt("Tags (comma separated)")
+        .allow_empty(true)
+        .interact_text()?;
+    profile.tags = split_tags(&tags);
+
     let config = RequestConfig::new(vec![(name, profile)].into_iter().collect());
 
-    let result = serde_yaml::to_string(&config)?;
+    let result = args.format.to_string(&config)?;
 
     let stdout = std::io::stdout();
 
     let mut stdout = stdout.lock();
 
     if atty::is(atty::Stream::Stdout) {
-        write!(stdout, "---\n{}", highlight_text(&result, "yaml", None)?)?;
+        let start = match args.format {
+            ConfigFormat::Yaml => "---\n",
+            _ => "",
+        };
+        write!(
+            stdout,
+            "{}{}",
+            start,
+            highlight_text(&result, args.format.extension(), None)?
+        )?;
     } else {
         write!(stdout, "{}", result)?
     }
@@ -1,33 +1,170 @@
 use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::process::ExitCode;
 
-use crate::ExtraArgs;
+use crate::{ConfigFormat, ExtraArgs, ProfileFilter};
 
+/// Command line of a binary, `R` holds the arguments of its `run`.
 #[derive(Parser, Debug, Clone)]
-pub struct Args {
+pub struct Args<R: clap::Args> {
     #[clap(subcommand)]
-    pub action: Action,
+    pub action: Action<R>,
 }
 
 #[derive(Subcommand, Debug, Clone)]
-pub enum Action {
-    /// Diff two api response base on given profiles
-    Run(RunArgs),
-    Parse,
+pub enum Action<R: clap::Args> {
+    /// Send the requests of the given profiles
+    Run(Box<R>),
+    Parse(ParseArgs),
+    /// Print the JSON Schema of the config file for editors
+    Schema,
+    /// Check every profile of the config and report all problems found
+    Lint(LintArgs),
 }
 
 #[derive(Parser, Debug, Clone)]
+pub struct LintArgs {
+    /// config file, can be repeated to merge the profiles of several files
+    #[clap(short, long, value_parser)]
+    pub config: Vec<String>,
+
+    /// template variable as key=value, overrides env files and the environment
+    #[clap(long = "var", value_parser = parse_var, number_of_values = 1)]
+    pub vars: Vec<(String, String)>,
+
+    /// dotenv file with template variables, can be repeated
+    #[clap(long, value_parser)]
+    pub env_file: Vec<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ParseArgs {
+    /// format of the generated config
+    #[clap(short, long, value_enum, default_value_t = ConfigFormat::Yaml)]
+    pub format: ConfigFormat,
+}
+
+/// `run` arguments shared by xdiff and xreq.
+#[derive(clap::Args, Debug, Clone)]
 pub struct RunArgs {
-    /// profile name
+    /// profile name or glob, can be repeated
     #[clap(short, long, value_parser)]
-    pub profile: String,
+    pub profile: Vec<String>,
+
+    /// run every profile in the config
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// only run profiles with one of these tags, can be repeated
+    #[clap(long, value_delimiter = ',')]
+    pub tag: Vec<String>,
+
+    /// skip profiles with any of these tags, can be repeated
+    #[clap(long, value_delimiter = ',')]
+    pub exclude_tag: Vec<String>,
+
+    /// number of profiles run at the same time
+    #[clap(long, value_parser = clap::value_parser!(u16).range(1..), default_value_t = 4)]
+    pub concurrency: u16,
 
     /// param overrides
     #[clap(short,long,value_parser=parse_key_val, number_of_values=1)]
     pub extra_params: Vec<KeyVal>,
 
+    /// config file, can be repeated to merge the profiles of several files
     #[clap(short, long, value_parser)]
-    pub config: Option<String>,
+    pub config: Vec<String>,
+
+    /// template variable as key=value, overrides env files and the environment
+    #[clap(long = "var", value_parser = parse_var, number_of_values = 1)]
+    pub vars: Vec<(String, String)>,
+
+    /// dotenv file with template variables, can be repeated
+    #[clap(long, value_parser)]
+    pub env_file: Vec<String>,
+
+    /// environment every req1, or every request of xreq, is sent to,
+    /// overrides `env` in the config
+    #[clap(long, value_parser)]
+    pub env1: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct DiffRunArgs {
+    #[clap(flatten)]
+    pub run: RunArgs,
+
+    /// environment every req2 is sent to, overrides `env` in the config
+    #[clap(long, value_parser)]
+    pub env2: Option<String>,
+
+    /// parts of the response whose differences fail the run, defaults to all
+    #[clap(long, value_enum, value_delimiter = ',')]
+    pub fail_on: Vec<DiffPart>,
+
+    /// how the diff is printed
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// how the text output is laid out
+    #[clap(long, value_enum, default_value_t = Layout::Unified)]
+    pub layout: Layout,
+
+    /// also write a self-contained html report to this file
+    #[clap(long, value_parser)]
+    pub report: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReqRunArgs {
+    #[clap(flatten)]
+    pub run: RunArgs,
+
+    /// send profiles one at a time in the order of --profile, values they
+    /// `extract` fill placeholders of the later ones
+    #[clap(long)]
+    pub sequence: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// json bodies as changes by path, everything else as a unified diff
+    Unified,
+    /// req1 and req2 in two columns sized to the terminal
+    SideBySide,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// colored diff for the terminal
+    Text,
+    /// structured report for other tools
+    Json,
+    /// RFC 6902 JSON Patch from the req1 body to the req2 body. Array
+    /// elements are patched by index and `compare` tolerances don't apply,
+    /// profiles with keyed or unordered `arrays` rules are rejected
+    Patch,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffPart {
+    Status,
+    Headers,
+    Body,
+}
+
+/// Process exit status, so CI can gate on the result of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    DiffFound = 1,
+    Error = 2,
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -71,6 +208,24 @@ fn parse_key_val(s: &str) -> Result<KeyVal> {
     })
 }
 
+fn parse_var(s: &str) -> Result<(String, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected key=value but got {}", s))?;
+    Ok((key.trim().to_string(), value.to_string()))
+}
+
+impl From<&RunArgs> for ProfileFilter {
+    fn from(args: &RunArgs) -> Self {
+        Self {
+            patterns: args.profile.clone(),
+            all: args.all,
+            tags: args.tag.clone(),
+            exclude_tags: args.exclude_tag.clone(),
+        }
+    }
+}
+
 impl From<Vec<KeyVal>> for ExtraArgs {
     fn from(args: Vec<KeyVal>) -> Self {
         let mut headers = vec![];
@@ -0,0 +1,424 @@
+use super::sign::hex;
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, Url};
+use ring::digest;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Credentials added to a request when it's sent, picked by `type`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    /// `Authorization: Basic` header from a username and password
+    Basic {
+        username: Secret,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        password: Option<Secret>,
+    },
+    /// `Authorization: Bearer` header with a token
+    Bearer { token: Secret },
+    /// A key sent in the header or query parameter `name`
+    ApiKey {
+        name: String,
+        value: Secret,
+        #[serde(rename = "in", default)]
+        location: KeyLocation,
+    },
+    /// Bearer token fetched from an OAuth2 token endpoint
+    #[serde(rename = "oauth2_client_credentials")]
+    OAuth2ClientCredentials(OAuth2),
+}
+
+/// OAuth2 client credentials grant, the token is fetched once per run and
+/// shared by every request using the same endpoint, client, secret and scope.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct OAuth2 {
+    pub token_url: Url,
+    pub client_id: Secret,
+    pub client_secret: Secret,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scope: Option<String>,
+    /// file the token is kept in until it expires, so later runs reuse it
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cache_file: Option<String>,
+}
+
+/// An access token along with what it was issued for.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct Token {
+    key: String,
+    access_token: String,
+    /// unix time in seconds, unknown when the endpoint didn't say
+    expires_at: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// Tokens fetched during this run by key. Each key has its own lock, held
+/// while fetching so concurrent requests wait for one token instead of
+/// fetching their own, without holding up other endpoints or clients.
+static TOKENS: Mutex<BTreeMap<String, Arc<Mutex<Option<Token>>>>> =
+    Mutex::const_new(BTreeMap::new());
+
+/// Tokens this close to expiring are fetched again.
+const EXPIRY_MARGIN: u64 = 30;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyLocation {
+    #[default]
+    Header,
+    Query,
+}
+
+/// A credential written in the config, or read from an environment variable
+/// or a file when the request is sent so it stays out of the config.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Secret {
+    Env {
+        env: String,
+    },
+    /// file holding the secret, trailing whitespace is dropped
+    File {
+        file: String,
+    },
+    Value(String),
+}
+
+impl Secret {
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Secret::Env { env } => std::env::var(env)
+                .with_context(|| format!("environment variable {} is not set", env)),
+            Secret::File { file } => {
+                let secret = std::fs::read_to_string(file)
+                    .with_context(|| format!("failed to read secret file {}", file))?;
+                Ok(secret.trim_end().to_string())
+            }
+            Secret::Value(value) => Ok(value.clone()),
+        }
+    }
+}
+
+impl Auth {
+    /// Add the credentials to the headers or the query of a request.
+    pub fn apply(&self, headers: &mut HeaderMap, query: &mut serde_json::Value) -> Result<()> {
+        match self {
+            Auth::Basic { username, password } => {
+                let password = match password {
+                    Some(password) => password.resolve()?,
+                    None => String::new(),
+                };
+                let credentials = STANDARD.encode(format!("{}:{}", username.resolve()?, password));
+                headers.insert(AUTHORIZATION, sensitive(format!("Basic {}", credentials))?);
+            }
+            Auth::Bearer { token } => {
+                headers.insert(
+                    AUTHORIZATION,
+                    sensitive(format!("Bearer {}", token.resolve()?))?,
+                );
+            }
+            Auth::ApiKey {
+                name,
+                value,
+                location: KeyLocation::Header,
+            } => {
+                headers.insert(HeaderName::from_str(name)?, sensitive(value.resolve()?)?);
+            }
+            Auth::ApiKey {
+                name,
+                value,
+                location: KeyLocation::Query,
+            } => {
+                query[name] = value.resolve()?.into();
+            }
+            // the token needs a request of its own, `send_with` adds it
+            Auth::OAuth2ClientCredentials(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl OAuth2 {
+    /// `Authorization` header with a token from the cache or the endpoint.
+    pub async fn authorization(&self, client: &Client) -> Result<HeaderValue> {
+        let token = self.token(client).await?;
+        sensitive(format!("Bearer {}", token.access_token))
+    }
+
+    async fn token(&self, client: &Client) -> Result<Token> {
+        let client_id = self.client_id.resolve()?;
+        // a rotated secret gets a token of its own
+        let secret = digest::digest(&digest::SHA256, self.client_secret.resolve()?.as_bytes());
+        let key = format!(
+            "{} {} {} {}",
+            self.token_url,
+            client_id,
+            self.scope.as_deref().unwrap_or_default(),
+            hex(secret.as_ref())
+        );
+
+        let slot = TOKENS.lock().await.entry(key.clone()).or_default().clone();
+        let mut slot = slot.lock().await;
+        if let Some(token) = slot.as_ref().filter(|t| t.is_fresh()) {
+            return Ok(token.clone());
+        }
+        let cached = match &self.cache_file {
+            Some(path) => read_cache(path, &key).await,
+            None => None,
+        };
+        let token = match cached {
+            Some(token) => token,
+            None => {
+                let token = self.fetch(client, &client_id, key).await?;
+                if let (Some(path), Some(_)) = (&self.cache_file, token.expires_at) {
+                    write_cache(path, &token)
+                        .await
+                        .with_context(|| format!("failed to write token cache {}", path))?;
+                }
+                token
+            }
+        };
+        *slot = Some(token.clone());
+        Ok(token)
+    }
+
+    async fn fetch(&self, client: &Client, client_id: &str, key: String) -> Result<Token> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope));
+        }
+        let res = client
+            .post(self.token_url.clone())
+            .basic_auth(client_id, Some(self.client_secret.resolve()?))
+            .form(&form)
+            .send()
+            .await
+            .with_context(|| format!("failed to fetch a token from {}", self.token_url))?;
+
+        let status = res.status();
+        let text = res.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!(
+                "token endpoint {} returned {}: {}",
+                self.token_url,
+                status,
+                text
+            ));
+        }
+        let response: TokenResponse = serde_json::from_str(&text)
+            .with_context(|| format!("invalid token response from {}", self.token_url))?;
+        Ok(Token {
+            key,
+            access_token: response.access_token,
+            expires_at: response.expires_in.map(|secs| now() + secs),
+        })
+    }
+}
+
+impl Token {
+    fn is_fresh(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| now() + EXPIRY_MARGIN < expires_at)
+    }
+}
+
+/// A fresh token for `key` from the cache file, a missing or stale cache
+/// just means fetching a new token.
+async fn read_cache(path: &str, key: &str) -> Option<Token> {
+    let content = fs::read_to_string(path).await.ok()?;
+    let token: Token = serde_json::from_str(&content).ok()?;
+    (token.key == key && token.expires_at.is_some() && token.is_fresh()).then_some(token)
+}
+
+/// Write the cache only the current user can read, through a temporary file
+/// so a concurrent run never reads it half written.
+async fn write_cache(path: &str, token: &Token) -> Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let tmp = format!(
+        "{}.{}-{}.tmp",
+        path,
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    );
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    file.write_all(serde_json::to_string(token)?.as_bytes())
+        .await?;
+    file.sync_all().await?;
+    fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// A header value that is left out of debug output.
+fn sensitive(value: String) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(&value)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn apply(yaml: &str) -> Result<(HeaderMap, serde_json::Value)> {
+        let auth: Auth = serde_yaml::from_str(yaml).unwrap();
+        let mut headers = HeaderMap::new();
+        let mut query = json!({});
+        auth.apply(&mut headers, &mut query)?;
+        Ok((headers, query))
+    }
+
+    #[test]
+    fn auth_should_read_secrets_from_env_and_files() {
+        std::env::set_var("XDIFF_TEST_AUTH_PASSWORD", "secret");
+        let (headers, _) =
+            apply("type: basic\nusername: alice\npassword: { env: XDIFF_TEST_AUTH_PASSWORD }\n")
+                .unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Basic YWxpY2U6c2VjcmV0");
+        assert!(headers[AUTHORIZATION].is_sensitive());
+
+        let file = std::env::temp_dir().join(format!("xdiff-token-{}", std::process::id()));
+        std::fs::write(&file, "abc\n").unwrap();
+        let (headers, _) = apply(&format!(
+            "type: bearer\ntoken: {{ file: {} }}\n",
+            file.display()
+        ))
+        .unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Bearer abc");
+        std::fs::remove_file(file).unwrap();
+
+        let err = apply("type: bearer\ntoken: { env: XDIFF_TEST_AUTH_MISSING }\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "environment variable XDIFF_TEST_AUTH_MISSING is not set"
+        );
+    }
+
+    fn oauth2(path: &str, client_id: &str, cache_file: Option<String>) -> OAuth2 {
+        OAuth2 {
+            token_url: format!("{}{}", mockito::server_url(), path)
+                .parse()
+                .unwrap(),
+            client_id: Secret::Value(client_id.into()),
+            client_secret: Secret::Value("secret".into()),
+            scope: Some("read".into()),
+            cache_file,
+        }
+    }
+
+    fn token_endpoint(path: &str, token: &str, hits: usize) -> mockito::Mock {
+        mockito::mock("POST", path)
+            .match_header("authorization", mockito::Matcher::Regex("^Basic ".into()))
+            .match_body("grant_type=client_credentials&scope=read")
+            .with_header("content-type", "application/json")
+            .with_body(json!({"access_token": token, "expires_in": 3600}).to_string())
+            .expect(hits)
+            .create()
+    }
+
+    #[tokio::test]
+    async fn oauth2_token_should_be_fetched_once_per_run() {
+        let endpoint = token_endpoint("/oauth/run", "t1", 1);
+        let auth = oauth2("/oauth/run", "run-client", None);
+        let client = Client::new();
+
+        for _ in 0..2 {
+            let header = auth.authorization(&client).await.unwrap();
+            assert_eq!(header, "Bearer t1");
+            assert!(header.is_sensitive());
+        }
+        endpoint.assert();
+    }
+
+    #[tokio::test]
+    async fn oauth2_token_should_be_cached_on_disk_until_expiry() {
+        let path = std::env::temp_dir().join(format!("xdiff-oauth2-{}.json", std::process::id()));
+        let cache_file = path.display().to_string();
+        let endpoint = token_endpoint("/oauth/disk", "t2", 1);
+
+        let mut auth = oauth2("/oauth/disk", "disk-client", Some(cache_file.clone()));
+        assert_eq!(auth.token(&Client::new()).await.unwrap().access_token, "t2");
+        endpoint.assert();
+        drop(endpoint);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // a rotated secret doesn't get the token of the old one
+        let endpoint = token_endpoint("/oauth/disk", "t3", 1);
+        auth.client_secret = Secret::Value("rotated".into());
+        assert_eq!(auth.token(&Client::new()).await.unwrap().access_token, "t3");
+        endpoint.assert();
+
+        // another run only finds the token on disk
+        let token: Token = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let key = token.key.clone();
+        assert_eq!(read_cache(&cache_file, &key).await, Some(token.clone()));
+        assert_eq!(read_cache(&cache_file, "other").await, None);
+
+        let expired = Token {
+            expires_at: Some(now() + 5),
+            ..token
+        };
+        std::fs::write(&path, serde_json::to_string(&expired).unwrap()).unwrap();
+        assert_eq!(read_cache(&cache_file, &key).await, None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn oauth2_should_report_endpoint_errors() {
+        let _m = mockito::mock("POST", "/oauth/denied")
+            .with_status(401)
+            .with_body("invalid_client")
+            .create();
+        let auth = oauth2("/oauth/denied", "denied-client", None);
+
+        let err = auth.authorization(&Client::new()).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("returned 401 Unauthorized: invalid_client"));
+    }
+
+    #[test]
+    fn api_key_should_go_to_header_or_query() {
+        let (headers, query) = apply("type: api_key\nname: x-api-key\nvalue: k1\n").unwrap();
+        assert_eq!(headers["x-api-key"], "k1");
+        assert_eq!(query, json!({}));
+
+        let (headers, query) =
+            apply("type: api_key\nname: api_key\nvalue: k2\nin: query\n").unwrap();
+        assert!(headers.is_empty());
+        assert_eq!(query, json!({"api_key": "k2"}));
+    }
+}
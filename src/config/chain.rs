@@ -0,0 +1,237 @@
+use super::RequestProfile;
+use crate::{ExtraArgs, JsonPath};
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName};
+use reqwest::{Client, StatusCode};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where a value is taken from in a response: `status`, `header:<name>` or a
+/// json path into the body such as `data.token`, `$.status` for a body field
+/// named status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Extract {
+    Status,
+    Header(HeaderName),
+    Body(JsonPath),
+}
+
+impl Extract {
+    /// The value in a response, `None` when the response doesn't have it.
+    /// Strings are taken as they are, other json values as json text.
+    pub fn find(&self, status: StatusCode, headers: &HeaderMap, body: &str) -> Option<String> {
+        match self {
+            Extract::Status => Some(status.as_u16().to_string()),
+            Extract::Header(name) => headers.get(name)?.to_str().ok().map(String::from),
+            Extract::Body(path) => {
+                let mut json: serde_json::Value = serde_json::from_str(body).ok()?;
+                let mut found = None;
+                path.for_each_mut(&mut json, |value| {
+                    found.get_or_insert_with(|| value.clone());
+                });
+                match found? {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(s) => Some(s),
+                    value => Some(value.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Values of the `extract` rules of a request in its response.
+pub fn extract_values(
+    rules: &BTreeMap<String, Extract>,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<BTreeMap<String, String>> {
+    rules
+        .iter()
+        .map(|(name, rule)| match rule.find(status, headers, body) {
+            Some(value) => Ok((name.clone(), value)),
+            None => Err(anyhow!(
+                "failed to extract {}: {} is not in the response",
+                name,
+                rule
+            )),
+        })
+        .collect()
+}
+
+/// Send `setup` steps in order, each one can use what the ones before it
+/// extracted. Returns every extracted value.
+pub(crate) async fn run_setup(
+    client: &Client,
+    steps: &[RequestProfile],
+) -> Result<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    for (i, step) in steps.iter().enumerate() {
+        let extracted = send_step(client, step, &values)
+            .await
+            .with_context(|| format!("setup step {} failed", i + 1))?;
+        values.extend(extracted);
+    }
+    Ok(values)
+}
+
+async fn send_step(
+    client: &Client,
+    step: &RequestProfile,
+    values: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>> {
+    let step = step.with_vars(values)?;
+    let res = step
+        .send_with(client, &ExtraArgs::default())
+        .await?
+        .into_inner();
+    let status = res.status();
+    if !status.is_success() {
+        return Err(anyhow!("{} {} returned {}", step.method, step.url, status));
+    }
+    let headers = res.headers().clone();
+    let body = res.text().await?;
+    extract_values(&step.extract, status, &headers, &body)
+}
+
+impl FromStr for Extract {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "status" {
+            return Ok(Extract::Status);
+        }
+        match s.strip_prefix("header:") {
+            Some(name) => Ok(Extract::Header(HeaderName::from_str(name.trim())?)),
+            None => Ok(Extract::Body(s.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for Extract {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Extract::Status => write!(f, "status"),
+            Extract::Header(name) => write!(f, "header:{}", name),
+            Extract::Body(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+impl Serialize for Extract {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Extract {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for Extract {
+    fn schema_name() -> Cow<'static, str> {
+        "Extract".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "minLength": 1,
+            "description": "`status`, `header:<name>` or a json path into the body"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+
+    #[test]
+    fn extract_should_find_status_headers_and_body_values() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-session", "s1".parse().unwrap());
+        let body = json!({"data": {"token": "t1", "ids": [3, 4]}, "status": "ok"}).to_string();
+        let find = |rule: &str| {
+            let rule: Extract = rule.parse().unwrap();
+            rule.find(StatusCode::CREATED, &headers, &body)
+        };
+
+        assert_eq!(find("status").as_deref(), Some("201"));
+        assert_eq!(find("$.status").as_deref(), Some("ok"));
+        assert_eq!(find("header:X-Session").as_deref(), Some("s1"));
+        assert_eq!(find("data.token").as_deref(), Some("t1"));
+        assert_eq!(find("data.ids").as_deref(), Some("[3,4]"));
+        assert_eq!(find("data.ids[*]").as_deref(), Some("3"));
+        assert_eq!(find("data.missing"), None);
+    }
+
+    #[tokio::test]
+    async fn run_setup_should_feed_values_into_later_steps() {
+        let login = mock("POST", "/chain/login")
+            .with_header("content-type", "application/json")
+            .with_body(json!({"access_token": "t1"}).to_string())
+            .create();
+        let create = mock("POST", "/chain/todos")
+            .match_header("authorization", "Bearer t1")
+            .match_body(Matcher::Json(json!({"owner": "t1"})))
+            .with_status(201)
+            .with_header("location", "/chain/todos/7")
+            .with_body(json!({"id": 7}).to_string())
+            .create();
+        let url = mockito::server_url();
+        let steps: Vec<RequestProfile> = serde_yaml::from_str(&format!(
+            r#"
+- method: POST
+  url: {url}/chain/login
+  extract:
+    token: access_token
+- method: POST
+  url: {url}/chain/todos
+  headers:
+    authorization: "Bearer {{{{ token }}}}"
+  body:
+    owner: "{{{{ token }}}}"
+  deferred: [token]
+  extract:
+    id: id
+    location: header:location
+"#
+        ))
+        .unwrap();
+
+        let values = run_setup(&Client::new(), &steps).await.unwrap();
+        login.assert();
+        create.assert();
+        assert_eq!(values["token"], "t1");
+        assert_eq!(values["id"], "7");
+        assert_eq!(values["location"], "/chain/todos/7");
+    }
+
+    #[tokio::test]
+    async fn run_setup_should_fail_on_missing_values() {
+        let _m = mock("GET", "/chain/empty")
+            .with_header("content-type", "application/json")
+            .with_body("{}")
+            .create();
+        let steps: Vec<RequestProfile> = serde_yaml::from_str(&format!(
+            "- url: {}/chain/empty\n  extract:\n    token: access_token\n",
+            mockito::server_url()
+        ))
+        .unwrap();
+
+        let err = run_setup(&Client::new(), &steps).await.unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "setup step 1 failed: failed to extract token: access_token is not in the response"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+
+/// A named target that requests point at with `env:` and `path:` instead of
+/// a full `url:`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct Environment {
+    pub base_url: Url,
+    /// sent with every request of the environment unless the request sets them
+    #[serde(
+        skip_serializing_if = "HeaderMap::is_empty",
+        with = "http_serde::header_map",
+        default
+    )]
+    #[schemars(schema_with = "super::headers_schema")]
+    pub headers: HeaderMap,
+}
+
+/// The `environments:` section of a config.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq)]
+pub struct Environments(pub BTreeMap<String, Environment>);
+
+impl Environments {
+    /// Read the `environments:` section of a raw config.
+    pub(crate) fn from_config(config: &Value) -> Result<Self> {
+        match config.get("environments") {
+            Some(section) => serde_yaml::from_value(section.clone())
+                .context("failed to parse the environments section"),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Turn `env` and `path` of the raw request at `key` into a full `url`,
+    /// `env` is replaced by `target` when it's given.
+    pub(crate) fn resolve_in(
+        &self,
+        profile: &mut Mapping,
+        key: &str,
+        target: Option<&str>,
+    ) -> Result<()> {
+        match profile.get_mut(key) {
+            Some(Value::Mapping(request)) => self.resolve(request, target).context(key.to_owned()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Same as `resolve_in` for a profile that is a request by itself.
+    pub(crate) fn resolve(&self, request: &mut Mapping, target: Option<&str>) -> Result<()> {
+        let Some(env) = request.remove("env") else {
+            return Ok(());
+        };
+        let name = match (target, env.as_str()) {
+            (Some(target), _) => target,
+            (None, Some(name)) => name,
+            (None, None) => return Err(anyhow!("env must be a string")),
+        };
+        if request.contains_key("url") {
+            return Err(anyhow!("use either url or env and path, not both"));
+        }
+        let env = self
+            .0
+            .get(name)
+            .ok_or_else(|| anyhow!("environment {} is not defined", name))?;
+
+        let path = match request.remove("path") {
+            Some(Value::String(path)) => path,
+            Some(_) => return Err(anyhow!("path must be a string")),
+            None => String::new(),
+        };
+        let url = format!(
+            "{}/{}",
+            env.base_url.as_str().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        );
+        request.insert("url".into(), url.into());
+
+        if !env.headers.is_empty() {
+            let headers = request
+                .entry("headers".into())
+                .or_insert_with(|| Value::Mapping(Mapping::new()));
+            let Value::Mapping(headers) = headers else {
+                return Err(anyhow!("headers must be a mapping"));
+            };
+            for (name, value) in &env.headers {
+                let exists = headers.keys().any(|k| {
+                    k.as_str()
+                        .is_some_and(|k| k.eq_ignore_ascii_case(name.as_str()))
+                });
+                if !exists {
+                    // values came from yaml strings, `to_str` would reject
+                    // anything but visible ascii
+                    let value = String::from_utf8_lossy(value.as_bytes());
+                    headers.insert(name.as_str().into(), value.as_ref().into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+environments:
+  prod:
+    base_url: https://api.example.com/v1/
+    headers:
+      x-env: prod
+      x-team: équipe
+      accept: application/json
+  staging:
+    base_url: https://staging.example.com/v1
+todo:
+  req1:
+    env: prod
+    path: /todos/1
+    headers:
+      Accept: text/plain
+  req2:
+    env: prod
+    path: todos/1
+"#;
+
+    fn resolve(target: Option<&str>) -> Result<Value> {
+        let mut config: Value = serde_yaml::from_str(CONFIG).unwrap();
+        let envs = Environments::from_config(&config)?;
+        let profile = config["todo"].as_mapping_mut().unwrap();
+        envs.resolve_in(profile, "req1", None)?;
+        envs.resolve_in(profile, "req2", target)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn environments_should_resolve_url_and_headers() {
+        let config = resolve(None).unwrap();
+        let req1 = &config["todo"]["req1"];
+        assert_eq!(req1["url"], "https://api.example.com/v1/todos/1");
+        assert_eq!(req1["headers"]["x-env"], "prod");
+        assert_eq!(req1["headers"]["x-team"], "équipe");
+        assert_eq!(req1["headers"]["Accept"], "text/plain");
+        assert!(req1["headers"].get("accept").is_none());
+        assert!(req1.get("env").is_none());
+        assert_eq!(
+            config["todo"]["req2"]["url"],
+            "https://api.example.com/v1/todos/1"
+        );
+    }
+
+    #[test]
+    fn environments_target_should_override_env() {
+        let config = resolve(Some("staging")).unwrap();
+        assert_eq!(
+            config["todo"]["req2"]["url"],
+            "https://staging.example.com/v1/todos/1"
+        );
+        assert!(config["todo"]["req2"].get("headers").is_none());
+
+        let err = resolve(Some("canary")).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "req2: environment canary is not defined"
+        );
+    }
+}
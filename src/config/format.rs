@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::path::Path;
+
+/// File format of a config, picked by file extension when it's loaded.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// `.json` and `.toml` files use their format, `.yaml`, `.yml` and files
+    /// without an extension are yaml. Other extensions are an error.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let Some(ext) = path.extension() else {
+            return Ok(Self::Yaml);
+        };
+        match ext.to_string_lossy().to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            ext => Err(anyhow!(
+                "unsupported extension .{} of config file {}, use .yaml, .yml, .json or .toml",
+                ext,
+                path.display()
+            )),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Yaml => "yaml",
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    /// Parse a config into the raw form templates and includes work on.
+    pub fn parse(&self, content: &str) -> Result<Value> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(content)?,
+            Self::Json => serde_json::from_str(content)?,
+            Self::Toml => toml::from_str(content)?,
+        })
+    }
+
+    pub fn to_string<T: Serialize>(&self, value: &T) -> Result<String> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::to_string(value)?,
+            Self::Json => serde_json::to_string_pretty(value)? + "\n",
+            Self::Toml => toml::to_string_pretty(value)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffConfig, LoadOptions};
+
+    const CONFIG: &str = r#"
+todo:
+  req1:
+    url: https://jsonplaceholder.typicode.com/todos/1
+    params:
+      a: 100
+    headers:
+      user-agent: xdiff
+  req2:
+    method: POST
+    url: https://jsonplaceholder.typicode.com/todos/2
+  res:
+    skip_headers:
+    - date
+    skip_body:
+    - id
+  tags:
+  - smoke
+"#;
+
+    #[test]
+    fn config_should_round_trip_through_every_format() {
+        let config = DiffConfig::from_yaml(CONFIG).unwrap();
+        let yaml = serde_yaml::to_string(&config).unwrap();
+
+        for format in [ConfigFormat::Json, ConfigFormat::Toml] {
+            let text = format.to_string(&config).unwrap();
+            let value = format.parse(&text).unwrap();
+            let parsed = DiffConfig::from_value_with(value, &LoadOptions::default()).unwrap();
+            assert_eq!(
+                serde_yaml::to_string(&parsed).unwrap(),
+                yaml,
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn config_format_should_follow_extension() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a/xdiff.toml")).unwrap(),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("xdiff.json")).unwrap(),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("xdiff.yml")).unwrap(),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("xdiff")).unwrap(),
+            ConfigFormat::Yaml
+        );
+        let err = ConfigFormat::from_path(Path::new("a/xdiff.jsn")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsupported extension .jsn of config file a/xdiff.jsn, use .yaml, .yml, .json or .toml"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use super::SECTIONS;
+use anyhow::{anyhow, Error, Result};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+
+/// Merge the `defaults:` section and `extends:` parents into every profile
+/// of a raw config, a profile overrides what it inherits.
+///
+/// Mappings are merged key by key, anything else including lists is
+/// replaced, so a profile can narrow the `skip_headers` it inherits.
+///
+/// A profile extending an unknown profile or itself, directly or not, is
+/// taken out of the config and returned with its error.
+pub(crate) fn resolve_inheritance(config: &mut Value) -> BTreeMap<String, Error> {
+    let mut failures = BTreeMap::new();
+    let Value::Mapping(map) = config else {
+        return failures;
+    };
+    let defaults = map.remove("defaults");
+
+    let mut profiles = BTreeMap::new();
+    for (name, profile) in map.iter() {
+        match name.as_str() {
+            Some(name) if !SECTIONS.contains(&name) => {
+                profiles.insert(name.to_string(), profile.clone());
+            }
+            _ => {}
+        }
+    }
+    let parents: BTreeMap<_, _> = profiles
+        .iter()
+        .map(|(name, profile)| {
+            let parent = profile.get("extends").and_then(Value::as_str);
+            (name.as_str(), parent)
+        })
+        .collect();
+
+    let mut resolved = BTreeMap::new();
+    for name in parents.keys() {
+        match check_chain(name, &parents) {
+            Ok(()) => {
+                resolve(name, &profiles, &parents, defaults.as_ref(), &mut resolved);
+            }
+            Err(e) => {
+                failures.insert(name.to_string(), e);
+            }
+        }
+    }
+    for name in failures.keys() {
+        map.remove(name.as_str());
+    }
+    for (name, profile) in resolved {
+        map.insert(name.into(), profile);
+    }
+    failures
+}
+
+/// Make sure every `extends` names an existing profile and no profile ends
+/// up inheriting from itself.
+pub(crate) fn check_extends(parents: &BTreeMap<&str, Option<&str>>) -> Result<()> {
+    for name in parents.keys() {
+        check_chain(name, parents)?;
+    }
+    Ok(())
+}
+
+/// Follow the parents of a profile up to one that extends nothing.
+fn check_chain(name: &str, parents: &BTreeMap<&str, Option<&str>>) -> Result<()> {
+    let mut chain = vec![name];
+    let mut current = name;
+    while let Some(parent) = parents[current] {
+        if !parents.contains_key(parent) {
+            return Err(anyhow!(
+                "profile {} extends unknown profile {}",
+                current,
+                parent
+            ));
+        }
+        let cycle = chain.contains(&parent);
+        chain.push(parent);
+        if cycle {
+            return Err(anyhow!(
+                "profiles extend each other: {}",
+                chain.join(" -> ")
+            ));
+        }
+        current = parent;
+    }
+    Ok(())
+}
+
+fn resolve<'a>(
+    name: &str,
+    profiles: &BTreeMap<String, Value>,
+    parents: &BTreeMap<&str, Option<&str>>,
+    defaults: Option<&Value>,
+    resolved: &'a mut BTreeMap<String, Value>,
+) -> &'a Value {
+    if !resolved.contains_key(name) {
+        let base = match parents[name] {
+            Some(parent) => Some(resolve(parent, profiles, parents, defaults, resolved).clone()),
+            None => defaults.cloned(),
+        };
+        let profile = profiles[name].clone();
+        let profile = match base {
+            Some(base) => merge(base, profile),
+            None => profile,
+        };
+        resolved.insert(name.to_string(), profile);
+    }
+    &resolved[name]
+}
+
+fn merge(base: Value, value: Value) -> Value {
+    match (base, value) {
+        (Value::Mapping(mut base), Value::Mapping(value)) => {
+            // a request sets its target with either `url` or `env` and `path`
+            if value.contains_key("url") {
+                base.remove("env");
+                base.remove("path");
+            }
+            if value.contains_key("env") {
+                base.remove("url");
+            }
+            for (k, v) in value {
+                let v = match base.remove(&k) {
+                    // credentials and signers of different kinds don't mix
+                    Some(_) if k == "auth" || k == "sign" => v,
+                    Some(b) => merge(b, v),
+                    None => v,
+                };
+                base.insert(k, v);
+            }
+            Value::Mapping(base)
+        }
+        (_, value) => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(yaml: &str) -> Result<Value> {
+        let mut config = serde_yaml::from_str(yaml).unwrap();
+        match resolve_inheritance(&mut config).into_values().next() {
+            Some(e) => Err(e),
+            None => Ok(config),
+        }
+    }
+
+    #[test]
+    fn resolve_inheritance_should_merge_defaults_and_parents() {
+        let config = resolved(
+            r#"
+defaults:
+  req1: { headers: { authorization: token } }
+  res: { skip_headers: [date, age] }
+base:
+  req1: { url: "http://localhost/a", params: { a: 1 } }
+  req2: { env: prod, path: /a }
+  res: { skip_headers: [etag] }
+child:
+  extends: base
+  req1: { params: { b: 2 } }
+  req2: { url: "http://localhost/b" }
+  res: { skip_headers: [date, server] }
+"#,
+        )
+        .unwrap();
+
+        assert!(config.get("defaults").is_none());
+        let child = &config["child"];
+        assert_eq!(child["req1"]["url"], "http://localhost/a");
+        assert_eq!(child["req1"]["headers"]["authorization"], "token");
+        assert_eq!(child["req1"]["params"]["a"], 1);
+        assert_eq!(child["req1"]["params"]["b"], 2);
+        assert_eq!(
+            serde_yaml::to_string(&child["req2"]).unwrap(),
+            "url: http://localhost/b\n"
+        );
+        assert_eq!(
+            serde_yaml::to_string(&child["res"]["skip_headers"]).unwrap(),
+            "- date\n- server\n"
+        );
+        assert_eq!(
+            serde_yaml::to_string(&config["base"]["res"]["skip_headers"]).unwrap(),
+            "- etag\n"
+        );
+    }
+
+    #[test]
+    fn resolve_inheritance_should_reject_cycles_and_unknown_parents() {
+        let err = resolved("a: {extends: b}\nb: {extends: c}\nc: {extends: a}\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "profiles extend each other: a -> b -> c -> a"
+        );
+
+        let err = resolved("a: {extends: b}\n").unwrap_err();
+        assert_eq!(err.to_string(), "profile a extends unknown profile b");
+
+        let mut config = serde_yaml::from_str("a: {extends: b}\nb: {extends: b}\nc: {}\n").unwrap();
+        let failures = resolve_inheritance(&mut config);
+        assert_eq!(failures.keys().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(
+            failures["a"].to_string(),
+            "profiles extend each other: a -> b -> b"
+        );
+        assert!(config.get("a").is_none());
+        assert!(config.get("c").is_some());
+    }
+}
@@ -0,0 +1,653 @@
+use super::loader::{is_key, merge_sources, read_sources, ParseFailure, Source};
+use super::{empty_json_value, ConfigFormat, LoadOptions, Resolved, SECTIONS};
+use crate::{RequestProfile, ResponseProfile};
+use anyhow::Result;
+use console::Style;
+use reqwest::header::HeaderName;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+const PROFILE_FIELDS: [&str; 6] = ["extends", "setup", "req1", "req2", "res", "tags"];
+const REQUEST_FIELDS: [&str; 10] = [
+    "method", "url", "params", "headers", "body", "auth", "sign", "extract", "deferred", "tags",
+];
+const RESPONSE_FIELDS: [&str; 5] = ["skip_headers", "skip_body", "mask", "arrays", "compare"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a config, located in its file when possible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<String>,
+    /// 1-based line and column, only known for yaml files
+    pub position: Option<(usize, usize)>,
+}
+
+/// Every problem found by linting a config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LintReport {
+    /// Whether the config fails to load, warnings alone don't count.
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+}
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for diagnostic in &self.diagnostics {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        match (self.count(Severity::Error), self.count(Severity::Warning)) {
+            (0, 0) => writeln!(f, "no problems found"),
+            (errors, warnings) => writeln!(
+                f,
+                "{}, {}",
+                plural(errors, "error"),
+                plural(warnings, "warning")
+            ),
+        }
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    match count {
+        1 => format!("1 {}", word),
+        _ => format!("{} {}s", count, word),
+    }
+}
+
+impl Diagnostic {
+    /// An error that stopped the config from loading at all.
+    fn from_error(error: &anyhow::Error) -> Self {
+        let file = error
+            .downcast_ref::<ParseFailure>()
+            .map(|failure| failure.0.display().to_string());
+        let cause = error.root_cause();
+        let position = if let Some(e) = cause.downcast_ref::<serde_yaml::Error>() {
+            e.location().map(|l| (l.line(), l.column()))
+        } else {
+            cause
+                .downcast_ref::<serde_json::Error>()
+                .map(|e| (e.line(), e.column()))
+        };
+        let message = match file {
+            Some(_) => cause.to_string(),
+            None => format!("{:#}", error),
+        };
+        Self {
+            severity: Severity::Error,
+            message,
+            file,
+            position,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file, self.position) {
+            (Some(file), Some((line, column))) => write!(f, "{}:{}:{}: ", file, line, column)?,
+            (Some(file), None) => write!(f, "{}: ", file)?,
+            _ => {}
+        }
+        let severity = match self.severity {
+            Severity::Error => Style::new().red().bold().apply_to("error"),
+            Severity::Warning => Style::new().yellow().bold().apply_to("warning"),
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Lint config files along with their includes.
+///
+/// Problems that keep the files from being read, like a yaml syntax error,
+/// end the lint. Every profile is checked on its own after `resolve` turned
+/// the raw config into what would be deserialized, a profile that fails to
+/// resolve is reported where it's defined.
+pub(crate) async fn lint_files(
+    paths: &[String],
+    options: &LoadOptions,
+    resolve: impl Fn(Value, &LoadOptions) -> Result<Resolved>,
+    check: impl Fn(&mut ProfileLint, &Value),
+) -> LintReport {
+    let mut report = LintReport::default();
+    let sources = match read_sources(paths).await {
+        Ok(sources) => sources,
+        Err(e) => {
+            report.diagnostics.push(Diagnostic::from_error(&e));
+            return report;
+        }
+    };
+    let (config, problems) = merge_sources(&sources);
+    report
+        .diagnostics
+        .extend(problems.iter().map(Diagnostic::from_error));
+    let resolved = match resolve(config, options) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            report.diagnostics.push(Diagnostic::from_error(&e));
+            return report;
+        }
+    };
+
+    let mut profiles: Vec<_> = resolved
+        .config
+        .as_mapping()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, profile)| Some((name.as_str()?, Ok(profile))))
+        .filter(|(name, _)| !SECTIONS.contains(name))
+        .chain(
+            resolved
+                .failures
+                .iter()
+                .map(|(name, e)| (name.as_str(), Err(e))),
+        )
+        .collect();
+    profiles.sort_by_key(|(name, _)| *name);
+    for (name, profile) in profiles {
+        let mut lint = ProfileLint {
+            name,
+            source: sources.iter().find(|s| s.config.contains_key(name)),
+            report: &mut report,
+        };
+        match profile {
+            Ok(profile) => check(&mut lint, profile),
+            Err(e) => lint.error(&[], format!("{:#}", e)),
+        }
+    }
+    report
+}
+
+/// Collects the problems of one profile, locating them in its source file.
+pub(crate) struct ProfileLint<'a> {
+    name: &'a str,
+    source: Option<&'a Source>,
+    report: &'a mut LintReport,
+}
+
+impl ProfileLint<'_> {
+    fn error(&mut self, path: &[&str], message: impl fmt::Display) {
+        self.push(Severity::Error, path, message);
+    }
+
+    fn warn(&mut self, path: &[&str], message: impl fmt::Display) {
+        self.push(Severity::Warning, path, message);
+    }
+
+    fn push(&mut self, severity: Severity, path: &[&str], message: impl fmt::Display) {
+        let mut field = self.name.to_string();
+        for segment in path {
+            match segment.parse::<usize>() {
+                Ok(index) => field.push_str(&format!("[{}]", index)),
+                Err(_) => field.push_str(&format!(".{}", segment)),
+            }
+        }
+        let path = join(&[self.name], path);
+        let position = self
+            .source
+            .filter(|source| source.format == ConfigFormat::Yaml)
+            .and_then(|source| locate(&source.content, &path));
+
+        self.report.diagnostics.push(Diagnostic {
+            severity,
+            message: format!("{}: {}", field, message),
+            file: self.source.map(|source| source.path.display().to_string()),
+            position,
+        });
+    }
+}
+
+/// Check a profile of an xdiff config.
+pub(crate) fn check_diff_profile(lint: &mut ProfileLint, profile: &Value) {
+    let Value::Mapping(map) = profile else {
+        lint.error(&[], "profile must be a mapping");
+        return;
+    };
+    check_fields(lint, &[], map, &PROFILE_FIELDS);
+    match map.get("setup") {
+        None | Some(Value::Null) => {}
+        Some(Value::Mapping(sides)) => {
+            check_fields(lint, &["setup"], sides, &["req1", "req2"]);
+            for side in ["req1", "req2"] {
+                check_setup(lint, &["setup", side], sides.get(side));
+            }
+        }
+        setup => check_setup(lint, &["setup"], setup),
+    }
+    for req in ["req1", "req2"] {
+        match map.get(req) {
+            Some(request) => check_request(lint, &[req], request),
+            None => lint.error(&[], format!("missing field `{}`", req)),
+        }
+    }
+    if let Some(res) = map.get("res") {
+        check_response(lint, &["res"], res);
+    }
+    check_typed::<Vec<String>>(lint, &["tags"], map.get("tags"));
+}
+
+/// Check a list of setup steps.
+fn check_setup(lint: &mut ProfileLint, path: &[&str], steps: Option<&Value>) {
+    match steps {
+        None | Some(Value::Null) => {}
+        Some(Value::Sequence(steps)) => {
+            for (i, step) in steps.iter().enumerate() {
+                check_request(lint, &join(path, &[&i.to_string()]), step);
+            }
+        }
+        Some(_) => lint.error(path, "must be a list of requests"),
+    }
+}
+
+/// Check a profile of an xreq config.
+pub(crate) fn check_request_profile(lint: &mut ProfileLint, profile: &Value) {
+    check_request(lint, &[], profile);
+}
+
+fn check_request(lint: &mut ProfileLint, path: &[&str], request: &Value) {
+    let Value::Mapping(map) = request else {
+        lint.error(path, "request must be a mapping");
+        return;
+    };
+    check_fields(lint, path, map, &REQUEST_FIELDS);
+    for key in ["params", "body"] {
+        match map.get(key) {
+            None | Some(Value::Null | Value::Mapping(_)) => {}
+            Some(_) => lint.error(&join(path, &[key]), "must be an object"),
+        }
+    }
+    if let Some(Value::Mapping(headers)) = map.get("headers") {
+        let mut seen = HashMap::new();
+        for name in headers.keys().filter_map(Value::as_str) {
+            if let Some(first) = seen.insert(name.to_ascii_lowercase(), name) {
+                lint.warn(
+                    &join(path, &["headers", name]),
+                    format!(
+                        "header is also set as {}, the later value replaces the earlier one",
+                        first
+                    ),
+                );
+            }
+        }
+    }
+
+    let Some(profile) = check_typed::<RequestProfile>(lint, path, Some(request)) else {
+        return;
+    };
+    if matches!(profile.method, Method::GET | Method::HEAD) && !empty_json_value(&profile.body) {
+        lint.warn(
+            &join(path, &["body"]),
+            format!(
+                "body is sent with a {} request, servers usually ignore it",
+                profile.method
+            ),
+        );
+    }
+}
+
+fn check_response(lint: &mut ProfileLint, path: &[&str], res: &Value) {
+    let Value::Mapping(map) = res else {
+        lint.error(path, "res must be a mapping");
+        return;
+    };
+    check_fields(lint, path, map, &RESPONSE_FIELDS);
+    let Some(profile) = check_typed::<ResponseProfile>(lint, path, Some(res)) else {
+        return;
+    };
+
+    for (i, pattern) in profile.skip_headers.iter().enumerate() {
+        let index = i.to_string();
+        let at = join(path, &["skip_headers", &index]);
+        let raw = pattern.to_string();
+        if profile.skip_headers[..i]
+            .iter()
+            .any(|p| p.to_string().eq_ignore_ascii_case(&raw))
+        {
+            lint.warn(&at, format!("{} is already skipped", raw));
+        } else if let Some(name) = pattern.exact_name() {
+            if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                lint.warn(
+                    &at,
+                    format!("{} is not a valid header name, it never matches", name),
+                );
+            }
+        }
+    }
+    for (i, skip) in profile.skip_body.iter().enumerate() {
+        if profile.skip_body[..i].contains(skip) {
+            let index = i.to_string();
+            lint.warn(
+                &join(path, &["skip_body", &index]),
+                format!("{} is already skipped", skip),
+            );
+        }
+    }
+
+    // rules on a skipped path never see a value
+    let rules = [
+        (
+            "mask",
+            profile.mask.iter().map(|r| &r.path).collect::<Vec<_>>(),
+        ),
+        ("arrays", profile.arrays.iter().map(|r| &r.path).collect()),
+        ("compare", profile.compare.iter().map(|r| &r.path).collect()),
+    ];
+    for (field, paths) in rules {
+        for (i, rule_path) in paths.into_iter().enumerate() {
+            if profile.skip_body.contains(rule_path) {
+                let index = i.to_string();
+                lint.warn(
+                    &join(path, &[field, &index]),
+                    format!(
+                        "{} is removed by skip_body, the rule never applies",
+                        rule_path
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Warn about keys serde silently ignores, they're usually typos.
+fn check_fields(lint: &mut ProfileLint, path: &[&str], map: &Mapping, known: &[&str]) {
+    for key in map.keys() {
+        match key.as_str() {
+            Some(key) if known.contains(&key) => {}
+            Some(key) => lint.warn(&join(path, &[key]), "unknown field, it is ignored"),
+            None => lint.warn(path, format!("{:?} is not a field name", key)),
+        }
+    }
+}
+
+/// Deserialize the value at `path`, reporting why it can't be.
+fn check_typed<T: DeserializeOwned>(
+    lint: &mut ProfileLint,
+    path: &[&str],
+    value: Option<&Value>,
+) -> Option<T> {
+    match serde_yaml::from_value(value?.clone()) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            lint.error(path, e);
+            None
+        }
+    }
+}
+
+fn join<'a>(path: &[&'a str], keys: &[&'a str]) -> Vec<&'a str> {
+    [path, keys].concat()
+}
+
+/// 1-based line and column of the value at `path` in a block style yaml
+/// file, or of the closest parent that can be found. Numeric segments are
+/// list items.
+fn locate(content: &str, path: &[&str]) -> Option<(usize, usize)> {
+    let mut found = None;
+    let mut depth = 0;
+    // indentation of the last key found and of its entries
+    let mut parent: Option<usize> = None;
+    let mut entries: Option<usize> = None;
+    let mut item = 0;
+
+    for (i, line) in content.lines().enumerate() {
+        let Some(segment) = path.get(depth) else {
+            break;
+        };
+        let text = line.trim_start();
+        if text.is_empty() || text.starts_with('#') || text.starts_with("---") {
+            continue;
+        }
+        let indent = line.len() - text.len();
+        let is_item = text == "-" || text.starts_with("- ");
+        // a list may sit at the indentation of its key
+        if parent.is_some_and(|p| indent < p || (indent == p && !is_item)) {
+            break;
+        }
+        if indent != *entries.get_or_insert(indent) {
+            continue;
+        }
+
+        let matched = match segment.parse::<usize>() {
+            // profile names are never list items
+            Ok(_) if depth == 0 => is_key(text, segment, ":"),
+            Ok(index) if is_item => {
+                item += 1;
+                item - 1 == index
+            }
+            Ok(_) => false,
+            Err(_) => is_key(text, segment, ":"),
+        };
+        if matched {
+            found = Some((i + 1, indent + 1));
+            depth += 1;
+            parent = Some(indent);
+            entries = None;
+            item = 0;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffConfig, RequestConfig};
+    use std::path::{Path, PathBuf};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xdiff-lint-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.display().to_string()
+    }
+
+    type Summary<'a> = (Severity, Option<(usize, usize)>, &'a str);
+
+    fn summary(report: &LintReport) -> Vec<Summary<'_>> {
+        report
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.position, d.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn locate_should_find_nested_keys_and_items() {
+        let content = "---\ntodo:\n  req1:\n    url: x\n  res:\n    skip_body:\n    - id\n    - name\nusers:\n  req1: { url: y }\n";
+        assert_eq!(locate(content, &["todo", "req1", "url"]), Some((4, 5)));
+        assert_eq!(
+            locate(content, &["todo", "res", "skip_body", "1"]),
+            Some((8, 5))
+        );
+        // flow mappings are located by their key
+        assert_eq!(locate(content, &["users", "req1", "url"]), Some((10, 3)));
+        // `url` of users isn't part of todo.res
+        assert_eq!(locate(content, &["todo", "res", "url"]), Some((5, 3)));
+        assert_eq!(locate(content, &["posts"]), None);
+    }
+
+    #[tokio::test]
+    async fn lint_should_report_every_profile() {
+        let dir = temp_dir("profiles");
+        let path = write(
+            &dir,
+            "xdiff.yaml",
+            r#"---
+todo:
+  req1:
+    url: http://localhost/todo/1
+    body:
+      a: 1
+  req2:
+    url: http://localhost/todo/2
+    params:
+      - c: 200
+    headers:
+      Accept: application/json
+      accept: text/html
+  res:
+    skip_header:
+      - date
+    skip_body:
+      - id
+      - id
+    mask:
+      - path: id
+users:
+  req1:
+    url: /users
+  req2:
+    url: http://localhost/users
+"#,
+        );
+
+        let report =
+            DiffConfig::lint_files(std::slice::from_ref(&path), &LoadOptions::default()).await;
+        let diagnostics = summary(&report);
+        assert_eq!(diagnostics.len(), 7, "{:#?}", diagnostics);
+        assert_eq!(
+            diagnostics[0],
+            (
+                Severity::Warning,
+                Some((5, 5)),
+                "todo.req1.body: body is sent with a GET request, servers usually ignore it"
+            )
+        );
+        assert_eq!(
+            diagnostics[1],
+            (
+                Severity::Error,
+                Some((9, 5)),
+                "todo.req2.params: must be an object"
+            )
+        );
+        assert_eq!(
+            diagnostics[2],
+            (
+                Severity::Warning,
+                Some((13, 7)),
+                "todo.req2.headers.accept: header is also set as Accept, the later value replaces the earlier one"
+            )
+        );
+        assert_eq!(
+            diagnostics[3],
+            (
+                Severity::Warning,
+                Some((15, 5)),
+                "todo.res.skip_header: unknown field, it is ignored"
+            )
+        );
+        assert_eq!(
+            diagnostics[4],
+            (
+                Severity::Warning,
+                Some((19, 7)),
+                "todo.res.skip_body[1]: id is already skipped"
+            )
+        );
+        assert_eq!(diagnostics[5].1, Some((21, 7)));
+        assert_eq!(diagnostics[6].0, Severity::Error);
+        assert!(diagnostics[6].2.starts_with("users.req1: "));
+        assert_eq!(diagnostics[6].1, Some((23, 3)));
+        assert!(report.has_errors());
+        assert_eq!(report.diagnostics[0].file.as_deref(), Some(path.as_str()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn lint_should_report_profiles_that_fail_to_resolve() {
+        let dir = temp_dir("resolve");
+        let other = write(&dir, "other.yaml", "ok:\n  url: http://localhost/b\n");
+        let path = write(
+            &dir,
+            "xreq.yaml",
+            r#"---
+include: other.yaml
+ok:
+  url: http://localhost/a
+templated:
+  url: http://{{ xdiff_lint_no_such_var }}/
+staging:
+  env: staging
+orphan:
+  extends: missing
+  url: http://localhost/c
+"#,
+        );
+
+        let report =
+            RequestConfig::lint_files(std::slice::from_ref(&path), &LoadOptions::default()).await;
+        let diagnostics = summary(&report);
+        assert_eq!(diagnostics.len(), 4, "{:#?}", diagnostics);
+        assert_eq!(
+            diagnostics[0].2,
+            format!("profile ok is defined in both {}:3 and {}:1", path, other)
+        );
+        assert_eq!(
+            diagnostics[1],
+            (
+                Severity::Error,
+                Some((9, 1)),
+                "orphan: profile orphan extends unknown profile missing"
+            )
+        );
+        assert_eq!(
+            diagnostics[2],
+            (
+                Severity::Error,
+                Some((7, 1)),
+                "staging: environment staging is not defined"
+            )
+        );
+        assert_eq!(
+            diagnostics[3],
+            (
+                Severity::Error,
+                Some((5, 1)),
+                "templated: failed to render templated.url: undefined variable xdiff_lint_no_such_var"
+            )
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn lint_should_locate_syntax_errors() {
+        let dir = temp_dir("syntax");
+        let path = write(&dir, "xreq.yaml", "todo:\n  url: [a\n");
+
+        let report =
+            RequestConfig::lint_files(std::slice::from_ref(&path), &LoadOptions::default()).await;
+        assert_eq!(report.diagnostics.len(), 1);
+        let diagnostic = &report.diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.file.as_deref(), Some(path.as_str()));
+        assert!(diagnostic.position.is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,390 @@
+use super::ConfigFormat;
+use crate::pattern::glob_match;
+use anyhow::{anyhow, Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Sections whose entries are merged across files, rather than the whole
+/// section being defined once.
+const MERGED_SECTIONS: [&str; 2] = ["vars", "environments"];
+
+/// Read config files and everything they `include:` into one raw config.
+///
+/// Includes are files or globs relative to the including file, a file is read
+/// once however often it's included. A profile, `defaults` or section entry
+/// defined in more than one file is an error naming both places.
+pub(crate) async fn load_files(paths: &[String]) -> Result<Value> {
+    let (config, problems) = merge_sources(&read_sources(paths).await?);
+    match problems.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(config),
+    }
+}
+
+/// A config file along with its top level keys, `include:` taken out.
+pub(crate) struct Source {
+    pub path: PathBuf,
+    pub content: String,
+    pub format: ConfigFormat,
+    pub config: Mapping,
+}
+
+/// Context of an error parsing a config file, so lint can point at the file.
+#[derive(Debug)]
+pub(crate) struct ParseFailure(pub PathBuf);
+
+impl fmt::Display for ParseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to parse config file {}", self.0.display())
+    }
+}
+
+/// Read config files and the files they include, in the order they're found.
+pub(crate) async fn read_sources(paths: &[String]) -> Result<Vec<Source>> {
+    let mut sources = vec![];
+    let mut loaded = HashSet::new();
+    let mut queue: VecDeque<PathBuf> = paths.iter().map(PathBuf::from).collect();
+
+    while let Some(path) = queue.pop_front() {
+        let canonical = fs::canonicalize(&path)
+            .await
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        if !loaded.insert(canonical) {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let format = ConfigFormat::from_path(&path)?;
+        let mut config = match format
+            .parse(&content)
+            .with_context(|| ParseFailure(path.clone()))?
+        {
+            Value::Mapping(map) => map,
+            Value::Null => Mapping::new(),
+            _ => return Err(anyhow!("config file {} is not a mapping", path.display())),
+        };
+
+        let source = Source {
+            path,
+            content,
+            format,
+            config: Mapping::new(),
+        };
+        if let Some(include) = config.remove("include") {
+            let dir = source.path.parent().unwrap_or_else(|| Path::new("."));
+            for pattern in include_patterns(include).context(source.origin("include"))? {
+                queue.extend(expand(dir, &pattern).await?);
+            }
+        }
+        sources.push(Source { config, ..source });
+    }
+    Ok(sources)
+}
+
+/// Merge the top level keys of every source. Keys defined more than once are
+/// returned as problems, the first definition is kept.
+pub(crate) fn merge_sources(sources: &[Source]) -> (Value, Vec<anyhow::Error>) {
+    let mut merged = Mapping::new();
+    let mut problems = vec![];
+    // where each profile, section and section entry was defined
+    let mut origins = HashMap::new();
+
+    for source in sources {
+        for (key, value) in &source.config {
+            let Some(name) = key.as_str() else {
+                problems.push(anyhow!(
+                    "{}: top level keys must be strings",
+                    source.path.display()
+                ));
+                continue;
+            };
+            match (name, value) {
+                (section, Value::Mapping(entries)) if MERGED_SECTIONS.contains(&section) => {
+                    let target = merged
+                        .entry(key.clone())
+                        .or_insert_with(|| Value::Mapping(Mapping::new()));
+                    let Value::Mapping(target) = target else {
+                        unreachable!("merged sections are always mappings")
+                    };
+                    for (entry, value) in entries {
+                        let entry = entry.as_str().unwrap_or_default().to_string();
+                        let what = format!("{}.{}", section, entry);
+                        let origin = source.entry_origin(section, &entry);
+                        match check_unique(&mut origins, &what, origin) {
+                            Ok(()) => {
+                                target.insert(entry.into(), value.clone());
+                            }
+                            Err(e) => problems.push(e),
+                        }
+                    }
+                }
+                (_, value) => {
+                    let what = match name {
+                        "defaults" | "vars" | "environments" => name.to_string(),
+                        _ => format!("profile {}", name),
+                    };
+                    match check_unique(&mut origins, &what, source.origin(name)) {
+                        Ok(()) => {
+                            merged.insert(key.clone(), value.clone());
+                        }
+                        Err(e) => problems.push(e),
+                    }
+                }
+            }
+        }
+    }
+    (Value::Mapping(merged), problems)
+}
+
+fn check_unique(origins: &mut HashMap<String, String>, what: &str, origin: String) -> Result<()> {
+    match origins.get(what) {
+        Some(first) => Err(anyhow!(
+            "{} is defined in both {} and {}",
+            what,
+            first,
+            origin
+        )),
+        None => {
+            origins.insert(what.to_string(), origin);
+            Ok(())
+        }
+    }
+}
+
+fn include_patterns(include: Value) -> Result<Vec<String>> {
+    match include {
+        Value::String(s) => Ok(vec![s]),
+        Value::Sequence(seq) => seq
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => Ok(s),
+                _ => Err(anyhow!("include must be a path or a list of paths")),
+            })
+            .collect(),
+        _ => Err(anyhow!("include must be a path or a list of paths")),
+    }
+}
+
+/// Resolve an include against `dir`, only its file name may be a glob.
+async fn expand(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = dir.join(pattern);
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    if !name.contains(['*', '?']) {
+        return Ok(vec![path]);
+    }
+    let parent = path.parent().unwrap_or(dir);
+    if parent.to_string_lossy().contains(['*', '?']) {
+        return Err(anyhow!(
+            "include {}: only the file name can be a glob",
+            pattern
+        ));
+    }
+
+    let mut paths = vec![];
+    let mut entries = fs::read_dir(parent)
+        .await
+        .with_context(|| format!("failed to read include directory {}", parent.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        if glob_match(name, &file_name.to_string_lossy()) && entry.file_type().await?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!("include {} matches no file", pattern));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+impl Source {
+    /// `path:line` of a top level key, or just the path when it can't be found.
+    fn origin(&self, key: &str) -> String {
+        let line = self.content.lines().position(|line| match self.format {
+            ConfigFormat::Yaml => is_key(line, key, ":"),
+            ConfigFormat::Json => is_key(line.trim_start(), key, ":"),
+            ConfigFormat::Toml => {
+                line.starts_with(&format!("[{}]", key))
+                    || line.starts_with(&format!("[{}.", key))
+                    || is_key(line, key, "=")
+            }
+        });
+        self.at(line)
+    }
+
+    /// `path:line` of an entry in a top level section.
+    fn entry_origin(&self, section: &str, key: &str) -> String {
+        let lines = || self.content.lines().enumerate();
+        let line = match self.format {
+            ConfigFormat::Yaml => {
+                let mut lines = lines();
+                lines
+                    .find(|(_, line)| is_key(line, section, ":"))
+                    .and_then(|_| {
+                        lines
+                            .take_while(|(_, line)| line.is_empty() || line.starts_with([' ', '#']))
+                            .find(|(_, line)| is_key(line.trim_start(), key, ":"))
+                    })
+            }
+            ConfigFormat::Json => {
+                let mut lines = lines();
+                lines
+                    .find(|(_, line)| is_key(line.trim_start(), section, ":"))
+                    .and_then(|_| lines.find(|(_, line)| is_key(line.trim_start(), key, ":")))
+            }
+            ConfigFormat::Toml => {
+                let table = format!("{}.{}", section, key);
+                lines()
+                    .find(|(_, line)| {
+                        line.starts_with(&format!("[{}]", table))
+                            || line.starts_with(&format!("[{}.", table))
+                    })
+                    .or_else(|| {
+                        let mut lines = lines();
+                        lines
+                            .find(|(_, line)| line.starts_with(&format!("[{}]", section)))
+                            .and_then(|_| {
+                                lines
+                                    .take_while(|(_, line)| !line.starts_with('['))
+                                    .find(|(_, line)| is_key(line, key, "="))
+                            })
+                    })
+            }
+        }
+        .map(|(i, _)| i);
+        self.at(line)
+    }
+
+    fn at(&self, line: Option<usize>) -> String {
+        match line {
+            Some(i) => format!("{}:{}", self.path.display(), i + 1),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
+/// Whether `line` starts with `key`, bare or quoted, followed by `separator`.
+pub(super) fn is_key(line: &str, key: &str, separator: &str) -> bool {
+    [
+        key.to_string(),
+        format!("\"{}\"", key),
+        format!("'{}'", key),
+    ]
+    .iter()
+    .any(|k| {
+        line.strip_prefix(k.as_str())
+            .is_some_and(|rest| rest.trim_start().starts_with(separator))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xdiff-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("teams")).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn load_files_should_follow_includes() {
+        let dir = temp_dir("include");
+        write(
+            &dir,
+            "xdiff.yaml",
+            "include:\n  - common.yaml\n  - teams/*.yaml\nvars:\n  host: localhost\n",
+        );
+        write(&dir, "common.yaml", "vars:\n  port: '80'\n");
+        write(&dir, "teams/a.yaml", "include: ../common.yaml\ntodo: {}\n");
+        write(&dir, "teams/b.yaml", "users: {}\n");
+
+        let path = dir.join("xdiff.yaml").display().to_string();
+        let config = load_files(&[path]).await.unwrap();
+        let mut keys: Vec<_> = config
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap())
+            .collect();
+        keys.sort();
+        assert_eq!(keys, ["todo", "users", "vars"]);
+        assert_eq!(config["vars"]["host"], "localhost");
+        assert_eq!(config["vars"]["port"], "80");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn load_files_should_reject_duplicates() {
+        let dir = temp_dir("duplicate");
+        write(&dir, "a.yaml", "---\ntodo: {}\nvars:\n  host: a\n");
+        write(&dir, "b.yaml", "users: {}\n\ntodo: {}\n");
+        write(&dir, "c.yaml", "vars:\n  # the api host\n  host: c\n");
+        let path = |name: &str| dir.join(name).display().to_string();
+
+        let err = load_files(&[path("a.yaml"), path("b.yaml")])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "profile todo is defined in both {}:2 and {}:3",
+                path("a.yaml"),
+                path("b.yaml")
+            )
+        );
+
+        let err = load_files(&[path("a.yaml"), path("c.yaml")])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "vars.host is defined in both {}:4 and {}:3",
+                path("a.yaml"),
+                path("c.yaml")
+            )
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn load_files_should_locate_duplicates_in_other_formats() {
+        let dir = temp_dir("formats");
+        write(
+            &dir,
+            "a.json",
+            "{\n  \"vars\": {\n    \"host\": \"a\"\n  }\n}\n",
+        );
+        write(
+            &dir,
+            "b.toml",
+            "[todo.req1]\nurl = 'x'\n\n[vars]\nport = 80\nhost = 'b'\n",
+        );
+        let path = |name: &str| dir.join(name).display().to_string();
+
+        let err = load_files(&[path("a.json"), path("b.toml")])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "vars.host is defined in both {}:3 and {}:6",
+                path("a.json"),
+                path("b.toml")
+            )
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
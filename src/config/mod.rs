@@ -1,46 +1,119 @@
-use crate::ExtraArgs;
-use anyhow::Result;
+use crate::pattern::glob_match;
+use crate::template::{render_extracted, render_extracted_url};
+use crate::{ArrayRule, CompareRule, ExtraArgs, HeaderPattern, JsonPath, MaskRule, TemplateVars};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
-use reqwest::{Client, Method, Response, Url};
+use reqwest::{Client, Method, Response, StatusCode, Url, Version};
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::fmt::Write;
+use serde_yaml::Mapping;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Write};
 use std::str::FromStr;
-use tokio::fs;
-
+use std::time::{Duration, Instant};
+
+pub mod auth;
+mod chain;
+pub mod environment;
+pub mod format;
+mod inherit;
+mod lint;
+mod loader;
+mod schema;
+pub mod sign;
 pub mod xdiff;
 pub mod xreq;
+pub use self::auth::*;
+pub(crate) use self::chain::run_setup;
+pub use self::chain::{extract_values, Extract};
+pub use self::environment::*;
+pub use self::format::*;
+use self::inherit::{check_extends, resolve_inheritance};
+use self::lint::{check_diff_profile, check_request_profile, lint_files};
+pub use self::lint::{Diagnostic, LintReport, Severity};
+use self::loader::load_files;
+use self::schema::*;
+pub use self::sign::*;
 pub use self::xdiff::*;
 pub use self::xreq::*;
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
 pub struct ResponseProfile {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub skip_headers: Vec<String>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub skip_body: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip_headers: Vec<HeaderPattern>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip_body: Vec<JsonPath>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub mask: Vec<MaskRule>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub arrays: Vec<ArrayRule>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub compare: Vec<CompareRule>,
 }
 
+/// A response along with the time it took for its headers to arrive and the
+/// url it was requested from, before any redirect.
 #[derive(Debug)]
-pub struct ResponseExt(Response);
+pub struct ResponseExt(Response, Duration, Url);
+
+/// A response with the `ResponseProfile` filters applied, ready to be diffed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilteredResponse {
+    /// Url the request was sent to, redirects aren't reflected
+    pub url: Url,
+    pub version: Version,
+    pub status: StatusCode,
+    /// Headers left after `skip_headers`
+    pub headers: HeaderMap,
+    pub body: ResponseBody,
+    pub elapsed: Duration,
+}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseBody {
+    Json(serde_json::Value),
+    Text(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[schemars(transform = request_schema)]
 pub struct RequestProfile {
     #[serde(with = "http_serde::method", default)]
+    #[schemars(schema_with = "method_schema")]
     pub method: Method,
     pub url: Url,
     #[serde(skip_serializing_if = "empty_json_value", default)]
+    #[schemars(schema_with = "object_schema")]
     pub params: Option<serde_json::Value>,
     #[serde(
         skip_serializing_if = "HeaderMap::is_empty",
         with = "http_serde::header_map",
         default
     )]
+    #[schemars(schema_with = "headers_schema")]
     pub headers: HeaderMap,
     #[serde(skip_serializing_if = "empty_json_value", default)]
+    #[schemars(schema_with = "object_schema")]
     pub body: Option<serde_json::Value>,
+    /// credentials added when the request is sent
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub auth: Option<Auth>,
+    /// signs the request right before it's sent, so it covers the final request
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sign: Option<Signer>,
+    /// values taken from the response, usable as `{{ name }}` in later requests
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub extract: BTreeMap<String, Extract>,
+    /// names whose placeholders wait for values extracted by earlier
+    /// requests, set when the config is loaded
+    #[serde(skip_serializing, default)]
+    #[schemars(skip)]
+    pub deferred: BTreeSet<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
 }
 
 impl RequestProfile {
@@ -64,13 +137,98 @@ impl RequestProfile {
         Ok(())
     }
     pub async fn send(&self, args: &super::ExtraArgs) -> Result<ResponseExt> {
-        let req = Client::new().request(self.method.clone(), self.url.clone());
+        self.send_with(&Client::new(), args).await
+    }
 
-        let (headers, query, body) = self.generate(args)?;
+    /// Send the request through `client`, sharing its connection pool.
+    pub async fn send_with(&self, client: &Client, args: &ExtraArgs) -> Result<ResponseExt> {
+        let (mut headers, mut query, body) = self.generate(args)?;
+        // credentials stay out of `generate`, so `get_url` never shows them
+        if let Some(auth) = &self.auth {
+            let mut credentials = HeaderMap::new();
+            let mut params = json!({});
+            auth.apply(&mut credentials, &mut params)
+                .context("failed to apply auth")?;
+            // what's given with `-e` still wins
+            let overridden = |name: &str| {
+                args.headers
+                    .iter()
+                    .any(|(k, _)| k.eq_ignore_ascii_case(name))
+            };
+            if let Auth::OAuth2ClientCredentials(oauth2) = auth {
+                if !overridden(header::AUTHORIZATION.as_str()) {
+                    credentials.insert(header::AUTHORIZATION, oauth2.authorization(client).await?);
+                }
+            }
+            for (name, value) in &credentials {
+                if !overridden(name.as_str()) {
+                    headers.insert(name, value.clone());
+                }
+            }
+            for (name, value) in params.as_object().into_iter().flatten() {
+                if !args.query.iter().any(|(k, _)| k == name) {
+                    query[name] = value.clone();
+                }
+            }
+        }
+
+        let mut req = client
+            .request(self.method.clone(), self.url.clone())
+            .headers(headers)
+            .query(&query)
+            .body(body)
+            .build()?;
+        if let Some(signer) = &self.sign {
+            signer.sign(&mut req).context("failed to sign request")?;
+        }
 
-        let res = req.headers(headers).query(&query).body(body).send().await?;
+        let url = req.url().clone();
+        let start = Instant::now();
+        let res = client.execute(req).await?;
 
-        Ok(ResponseExt(res))
+        Ok(ResponseExt(res, start.elapsed(), url))
+    }
+
+    /// Fill the `{{ name }}` placeholders of values extracted from earlier
+    /// responses into the url, headers, params and body. Fails when a name
+    /// the request waits for has no value.
+    pub fn with_vars(&self, values: &BTreeMap<String, String>) -> Result<Self> {
+        let values = self
+            .deferred
+            .iter()
+            .map(|name| match values.get(name) {
+                Some(value) => Ok((name.clone(), value.clone())),
+                None => Err(anyhow::anyhow!(
+                    "undefined variable {}, no earlier request extracts it",
+                    name
+                )),
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+        let mut profile = self.clone();
+        profile.deferred.clear();
+        if values.is_empty() {
+            return Ok(profile);
+        }
+        let url = render_extracted_url(self.url.as_str(), &values);
+        if url != self.url.as_str() {
+            profile.url = url
+                .parse()
+                .with_context(|| format!("invalid url {}", url))?;
+        }
+        for (name, value) in profile.headers.iter_mut() {
+            let Ok(text) = value.to_str() else {
+                continue;
+            };
+            let rendered = render_extracted(text, &values);
+            if rendered != text {
+                *value = HeaderValue::from_str(&rendered)
+                    .with_context(|| format!("invalid value of header {}", name))?;
+            }
+        }
+        for json in profile.params.iter_mut().chain(profile.body.iter_mut()) {
+            render_json(json, &values);
+        }
+        Ok(profile)
     }
 
     pub fn get_url(&self, args: &ExtraArgs) -> Result<String> {
@@ -84,6 +242,8 @@ impl RequestProfile {
         Ok(url.to_string())
     }
 
+    /// Headers, query and body of the request, without the credentials of
+    /// `auth` which only `send_with` adds.
     pub fn generate(&self, args: &ExtraArgs) -> Result<(HeaderMap, serde_json::Value, String)> {
         let mut headers = self.headers.clone();
         let mut body = self.body.clone().unwrap_or_else(|| json!({}));
@@ -138,10 +298,21 @@ impl RequestProfile {
             params,
             headers,
             body,
+            auth: None,
+            sign: None,
+            extract: BTreeMap::new(),
+            deferred: BTreeSet::new(),
+            tags: vec![],
         }
     }
 }
 
+impl Tagged for RequestProfile {
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
 impl FromStr for RequestProfile {
     type Err = anyhow::Error;
 
@@ -162,48 +333,35 @@ impl FromStr for RequestProfile {
             params: Some(params),
             headers: HeaderMap::new(),
             body: None,
+            auth: None,
+            sign: None,
+            extract: BTreeMap::new(),
+            deferred: BTreeSet::new(),
+            tags: vec![],
         })
     }
 }
 
 impl ResponseExt {
-    pub async fn filter_text(self, profile: &ResponseProfile) -> Result<String> {
-        fn filter_json(text: &str, skip: &Vec<String>) -> Result<String> {
-            let json: serde_json::Value = serde_json::from_str(text)?;
-
-            match json {
-                serde_json::Value::Object(mut j) => {
-                    for k in skip {
-                        j[k] = json!(null);
-                    }
-                    Ok(serde_json::to_string_pretty(&j)?)
-                }
-                _ => Ok(String::new()),
-            }
-        }
-
-        let mut output = String::new();
-        write!(output, "{:?}{}\r", self.0.version(), self.0.status())?;
-        let headers = self.0.headers();
-
-        for (k, v) in headers.iter() {
-            if !profile.skip_headers.contains(&k.to_string()) {
-                write!(output, "{}:{:?}\r", k, v)?;
-            }
-        }
-
-        let content_type = get_content_type(headers);
-        let text = self.0.text().await?;
-
-        match content_type.as_deref() {
-            Some("application/json") => {
-                let text = filter_json(&text, &profile.skip_body)?;
-                output.push_str(&text);
-            }
-            _ => output.push_str(&text),
-        }
+    pub async fn filter(self, profile: &ResponseProfile) -> Result<FilteredResponse> {
+        let url = self.2;
+        let version = self.0.version();
+        let status = self.0.status();
+        let headers = filter_headers(self.0.headers(), &profile.skip_headers);
+        let body = get_body(self.0, profile).await?;
+
+        Ok(FilteredResponse {
+            url,
+            version,
+            status,
+            headers,
+            body,
+            elapsed: self.1,
+        })
+    }
 
-        Ok(output)
+    pub async fn filter_text(self, profile: &ResponseProfile) -> Result<String> {
+        self.filter(profile).await?.to_text()
     }
 
     pub fn get_headers_keys(&self) -> Vec<String> {
@@ -227,59 +385,259 @@ fn get_content_type(headers: &HeaderMap) -> Option<String> {
 }
 
 fn empty_json_value(v: &Option<serde_json::Value>) -> bool {
-    v.as_ref().map_or(true, |v| {
-        v.is_null() || (v.is_object() && v.as_object().unwrap().is_empty())
-    })
+    v.as_ref()
+        .is_none_or(|v| v.is_null() || (v.is_object() && v.as_object().unwrap().is_empty()))
 }
 
 pub fn get_status_text(res: &Response) -> Result<String> {
-    Ok(format!("{:?} {}\n", res.version(), res.status()))
+    Ok(status_text(res.version(), res.status()))
+}
+
+pub fn get_header_text(res: &Response, skip_headers: &[HeaderPattern]) -> Result<String> {
+    header_text(&filter_headers(res.headers(), skip_headers))
 }
 
-pub fn get_header_text(res: &Response, skip_headers: &[String]) -> Result<String> {
+fn status_text(version: Version, status: StatusCode) -> String {
+    format!("{:?} {}\n", version, status)
+}
+
+fn header_text(headers: &HeaderMap) -> Result<String> {
     let mut output = String::new();
 
-    let headers = res.headers();
     for (k, v) in headers.iter() {
-        if !skip_headers.iter().any(|sh| sh == k.as_str()) {
-            writeln!(&mut output, "{}: {:?}", k, v)?;
-        }
+        writeln!(&mut output, "{}: {:?}", k, v)?;
     }
     writeln!(&mut output)?;
 
     Ok(output)
 }
 
-pub async fn get_body_text(res: Response, skip_body: &[String]) -> Result<String> {
+fn filter_headers(headers: &HeaderMap, skip_headers: &[HeaderPattern]) -> HeaderMap {
+    headers
+        .iter()
+        .filter(|(k, _)| !skip_headers.iter().any(|sh| sh.matches(k.as_str())))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+pub async fn get_body_text(res: Response, skip_body: &[JsonPath]) -> Result<String> {
+    let profile = ResponseProfile::new(vec![], skip_body.to_vec());
+    get_body(res, &profile).await?.to_text()
+}
+
+async fn get_body(res: Response, profile: &ResponseProfile) -> Result<ResponseBody> {
     let content_type = get_content_type(res.headers());
     let text = res.text().await?;
 
     match content_type.as_deref() {
-        Some("application/json") => filter_json(&text, skip_body),
-        _ => Ok(text),
+        // a body that claims to be json but doesn't parse is still diffed as text
+        Some("application/json") => match serde_json::from_str(&text) {
+            Ok(mut json) => {
+                profile.filter_json(&mut json);
+                Ok(ResponseBody::Json(json))
+            }
+            Err(_) => Ok(ResponseBody::Text(text)),
+        },
+        _ => Ok(ResponseBody::Text(text)),
     }
 }
-fn filter_json(text: &str, skip: &[String]) -> Result<String> {
-    let mut json: serde_json::Value = serde_json::from_str(text)?;
 
-    // For now we just ignore non-object values, we don't know how to filter.
-    // In future, we might support array of objects
-    if let serde_json::Value::Object(ref mut obj) = json {
-        for k in skip {
-            obj.remove(k);
+impl ResponseBody {
+    pub fn to_text(&self) -> Result<String> {
+        match self {
+            ResponseBody::Json(json) => Ok(serde_json::to_string_pretty(json)?),
+            ResponseBody::Text(text) => Ok(text.clone()),
+        }
+    }
+
+    /// The body as a json value, text bodies become a single string.
+    pub fn to_value(&self) -> serde_json::Value {
+        match self {
+            ResponseBody::Json(json) => json.clone(),
+            ResponseBody::Text(text) => serde_json::Value::String(text.clone()),
         }
     }
+}
 
-    Ok(serde_json::to_string_pretty(&json)?)
+impl FilteredResponse {
+    /// Status line followed by the headers
+    pub fn head(&self) -> Result<String> {
+        Ok(format!(
+            "{}{}",
+            status_text(self.version, self.status),
+            header_text(&self.headers)?
+        ))
+    }
+
+    pub fn to_text(&self) -> Result<String> {
+        Ok(format!("{}{}", self.head()?, self.body.to_text()?))
+    }
 }
 
 impl ResponseProfile {
-    pub fn new(skip_headers: Vec<String>, skip_body: Vec<String>) -> Self {
+    pub fn new(skip_headers: Vec<HeaderPattern>, skip_body: Vec<JsonPath>) -> Self {
         Self {
             skip_headers,
             skip_body,
+            ..Default::default()
+        }
+    }
+
+    /// Drop the skipped paths and mask volatile values of a json body.
+    pub fn filter_json(&self, json: &mut serde_json::Value) {
+        for path in &self.skip_body {
+            path.remove(json);
+        }
+        for rule in &self.mask {
+            rule.apply(json);
+        }
+    }
+}
+
+/// Which profiles of a config to run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileFilter {
+    /// profile names or globs
+    pub patterns: Vec<String>,
+    pub all: bool,
+    /// keep profiles carrying any of these tags
+    pub tags: Vec<String>,
+    /// drop profiles carrying any of these tags
+    pub exclude_tags: Vec<String>,
+}
+
+impl fmt::Display for ProfileFilter {
+    /// The given parts of the filter, as their command line flags.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if self.all {
+            parts.push("--all".to_string());
+        }
+        for pattern in &self.patterns {
+            parts.push(format!("--profile {}", pattern));
+        }
+        if !self.tags.is_empty() {
+            parts.push(format!("--tag {}", self.tags.join(",")));
+        }
+        if !self.exclude_tags.is_empty() {
+            parts.push(format!("--exclude-tag {}", self.exclude_tags.join(",")));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+pub trait Tagged {
+    fn tags(&self) -> &[String];
+}
+
+/// Select the profiles matching any of the names or globs of `filter`, or
+/// every profile with `all`, sorted by name so runs are deterministic.
+///
+/// Names and tags narrow each other down, so `--tag smoke` alone runs every
+/// smoke profile while `-p 'todo*' --tag smoke` runs only the smoke todos.
+pub fn select_profiles<'a, T: Tagged>(
+    profiles: &'a HashMap<String, T>,
+    filter: &ProfileFilter,
+) -> Result<Vec<(&'a str, &'a T)>> {
+    if !filter.all && filter.patterns.is_empty() && filter.tags.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No profile given, use --profile, --tag or --all"
+        ));
+    }
+    for pattern in &filter.patterns {
+        if !profiles.keys().any(|name| glob_match(pattern, name)) {
+            return Err(anyhow::anyhow!("Profile {} is not found", pattern));
         }
     }
+
+    let has_tag = |profile: &T, tags: &[String]| profile.tags().iter().any(|t| tags.contains(t));
+    let mut selected: Vec<_> = profiles
+        .iter()
+        .filter(|(name, _)| {
+            filter.all
+                || filter.patterns.is_empty()
+                || filter.patterns.iter().any(|p| glob_match(p, name))
+        })
+        .filter(|(_, profile)| filter.tags.is_empty() || has_tag(profile, &filter.tags))
+        .filter(|(_, profile)| !has_tag(profile, &filter.exclude_tags))
+        .map(|(name, profile)| (name.as_str(), profile))
+        .collect();
+    if selected.is_empty() {
+        return Err(anyhow::anyhow!("No profile matches {}", filter));
+    }
+    selected.sort_by_key(|(name, _)| *name);
+    Ok(selected)
+}
+
+/// How a config is turned into profiles when it's loaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    pub vars: TemplateVars,
+    /// environment req1 is sent to, in place of the one in the config
+    pub env1: Option<String>,
+    /// environment req2 is sent to, in place of the one in the config
+    pub env2: Option<String>,
+    /// profiles are sent in sequence, so the values any of them `extract`s
+    /// can be used by the others
+    pub sequence: bool,
+}
+
+/// Top level keys of a config that are sections rather than profiles.
+pub(crate) const SECTIONS: [&str; 3] = ["vars", "environments", "defaults"];
+
+/// A raw config with its profiles resolved, less the ones that failed.
+pub(crate) struct Resolved {
+    pub config: serde_yaml::Value,
+    /// profiles that failed to resolve by name, along with their error
+    pub failures: BTreeMap<String, anyhow::Error>,
+}
+
+impl Resolved {
+    /// The config when every profile resolved, else the first failure.
+    pub(crate) fn into_config(self) -> Result<serde_yaml::Value> {
+        match self.failures.into_iter().next() {
+            Some((name, e)) => Err(e.context(format!("profile: {}", name))),
+            None => Ok(self.config),
+        }
+    }
+}
+
+/// Resolve the inherited fields and `{{ var }}` placeholders of a raw config,
+/// letting `resolve` turn `env` references of each profile into urls.
+///
+/// Each profile is resolved on its own, so one that fails doesn't keep the
+/// others from being checked. Problems outside the profiles fail the config.
+fn resolve_template(
+    mut value: serde_yaml::Value,
+    options: &LoadOptions,
+    resolve: impl Fn(&Environments, &mut Mapping) -> Result<()>,
+) -> Result<Resolved> {
+    if value.get("include").is_some() {
+        return Err(anyhow::anyhow!("include is only supported in config files"));
+    }
+    let mut failures = resolve_inheritance(&mut value);
+    failures.extend(options.vars.render_config(&mut value, options.sequence)?);
+
+    let envs = Environments::from_config(&value)?;
+    if let serde_yaml::Value::Mapping(map) = &mut value {
+        for (name, profile) in map.iter_mut() {
+            let name = name.as_str().unwrap_or_default();
+            if let (false, serde_yaml::Value::Mapping(profile)) =
+                (SECTIONS.contains(&name), profile)
+            {
+                if let Err(e) = resolve(&envs, profile) {
+                    failures.insert(name.to_string(), e);
+                }
+            }
+        }
+        for name in failures.keys() {
+            map.remove(name.as_str());
+        }
+    }
+    Ok(Resolved {
+        config: value,
+        failures,
+    })
 }
 
 #[async_trait]
@@ -288,16 +646,67 @@ where
     Self: ValidateConfig + DeserializeOwned,
 {
     async fn load_yaml(path: &str) -> Result<Self> {
-        println!("{}", path);
-        let content = fs::read_to_string(path).await?;
-        Self::from_yaml(&content)
+        Self::load_yaml_with(path, &LoadOptions::default()).await
+    }
+
+    async fn load_yaml_with(path: &str, options: &LoadOptions) -> Result<Self> {
+        Self::load_yaml_files(&[path.to_string()], options).await
+    }
+
+    /// Load and merge several config files along with their includes.
+    async fn load_yaml_files(paths: &[String], options: &LoadOptions) -> Result<Self> {
+        for path in paths {
+            println!("{}", path);
+        }
+        let value = load_files(paths).await?;
+        Self::from_value_with(value, options)
     }
 
     fn from_yaml(content: &str) -> Result<Self> {
-        let config: Self = serde_yaml::from_str(content)?;
+        Self::from_yaml_with(content, &LoadOptions::default())
+    }
+
+    fn from_yaml_with(content: &str, options: &LoadOptions) -> Result<Self> {
+        Self::from_value_with(serde_yaml::from_str(content)?, options)
+    }
+
+    fn from_value_with(value: serde_yaml::Value, options: &LoadOptions) -> Result<Self> {
+        let config: Self = serde_yaml::from_value(Self::resolve_value(value, options)?)?;
         config.validate()?;
         Ok(config)
     }
+
+    /// Resolve templates, inheritance and environments of a raw config.
+    fn resolve_value(value: serde_yaml::Value, options: &LoadOptions) -> Result<serde_yaml::Value> {
+        resolve_requests(value, options)?.into_config()
+    }
+}
+
+/// Resolve a raw config whose profiles are requests, like xreq's.
+pub(crate) fn resolve_requests(
+    value: serde_yaml::Value,
+    options: &LoadOptions,
+) -> Result<Resolved> {
+    resolve_template(value, options, |envs, profile| {
+        envs.resolve(profile, options.env1.as_deref())
+    })
+}
+
+fn render_json(value: &mut serde_json::Value, values: &BTreeMap<String, String>) {
+    match value {
+        serde_json::Value::String(s) => *s = render_extracted(s, values),
+        serde_json::Value::Array(items) => {
+            for item in items {
+                render_json(item, values);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for item in map.values_mut() {
+                render_json(item, values);
+            }
+        }
+        _ => {}
+    }
 }
 
 pub trait ValidateConfig {
@@ -332,6 +741,66 @@ mod tests {
         assert_eq!(res.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn response_filter_should_parse_json_body() {
+        let _m = mock_for_url("/todo?a=3", json!({"id":1,"title":2}));
+
+        let profile = ResponseProfile::new(
+            vec!["Content-*".parse().unwrap()],
+            vec!["id".parse().unwrap()],
+        );
+        let res = get_response("/todo?a=3", &Default::default())
+            .await
+            .filter(&profile)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(!res.head().unwrap().contains("content-"));
+        assert_eq!(res.body, ResponseBody::Json(json!({"title":2})));
+    }
+
+    #[tokio::test]
+    async fn request_profile_should_keep_query_credentials_out_of_url() {
+        let _m = mock("GET", "/todo?a=1&api_key=secret-key")
+            .with_status(200)
+            .create();
+        let mut profile = get_profile("/todo?a=1");
+        profile.auth = Some(
+            serde_yaml::from_str("type: api_key\nname: api_key\nvalue: secret-key\nin: query\n")
+                .unwrap(),
+        );
+
+        let url = profile.get_url(&Default::default()).unwrap();
+        assert_eq!(url, get_url("/todo?a=1"));
+        assert!(!url.contains("secret-key"));
+
+        let res = profile
+            .send(&Default::default())
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn response_filter_should_keep_requested_url() {
+        let _m = mock("GET", "/moved")
+            .with_status(302)
+            .with_header("location", "/todo?a=4")
+            .create();
+        let _m2 = mock_for_url("/todo?a=4", json!({"id":1}));
+
+        let res = get_response("/moved", &Default::default())
+            .await
+            .filter(&ResponseProfile::default())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.url.as_str(), get_url("/moved"));
+    }
+
     #[test]
     fn request_profile_get_url_should_work() {
         let profile = get_profile("/todo?a=1&b=2");
@@ -351,6 +820,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn request_profile_with_vars_should_fill_url_headers_and_body() {
+        let options = LoadOptions {
+            sequence: true,
+            ..Default::default()
+        };
+        let config = RequestConfig::from_yaml_with(
+            r#"
+login:
+  url: http://localhost/login
+  extract: { id: id, user: user, token: token }
+todo:
+  url: http://localhost/todos/{{ id }}/{{{{done}}
+  params:
+    owner: "{{ user }}"
+  headers:
+    authorization: Bearer {{ token }}
+  body:
+    title: todo {{ id }}
+"#,
+            &options,
+        )
+        .unwrap();
+        let profile = &config.profiles["todo"];
+        let values = [("id", "7 8"), ("user", "u1"), ("token", "t1")]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .into();
+
+        let filled = profile.with_vars(&values).unwrap();
+        assert_eq!(
+            filled.url.as_str(),
+            "http://localhost/todos/7%208/%7B%7Bdone%7D%7D"
+        );
+        assert_eq!(filled.params, Some(json!({"owner": "u1"})));
+        assert_eq!(filled.headers["authorization"], "Bearer t1");
+        assert_eq!(filled.body, Some(json!({"title": "todo 7 8"})));
+    }
+
+    #[test]
+    fn request_profile_with_vars_should_fail_on_unfilled_names() {
+        let options = LoadOptions {
+            sequence: true,
+            ..Default::default()
+        };
+        let config = RequestConfig::from_yaml_with(
+            r#"
+login:
+  url: http://localhost/login
+  headers: { x-session: "{{ session }}" }
+  extract: { token: token }
+todos:
+  url: http://localhost/todos
+  extract: { session: header:x-session }
+"#,
+            &options,
+        )
+        .unwrap();
+        let login = &config.profiles["login"];
+        assert!(config.profiles["todos"].with_vars(&BTreeMap::new()).is_ok());
+
+        let values = [("token".to_string(), "t1".to_string())].into();
+        let err = login.with_vars(&values).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "undefined variable session, no earlier request extracts it"
+        );
+    }
+
     fn mock_for_url(path_and_query: &str, resp_body: serde_json::Value) -> Mock {
         mock("GET", path_and_query)
             .with_status(200)
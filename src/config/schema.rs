@@ -0,0 +1,122 @@
+use schemars::{json_schema, Schema, SchemaGenerator};
+use serde_json::json;
+
+pub(crate) fn method_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "string",
+        "enum": ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS", "TRACE", "CONNECT"],
+        "default": "GET"
+    })
+}
+
+pub(crate) fn headers_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "object",
+        "additionalProperties": { "type": "string" }
+    })
+}
+
+/// Numbers and booleans in `vars` are turned into strings when loading.
+pub(crate) fn vars_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "object",
+        "additionalProperties": { "type": ["string", "number", "boolean"] }
+    })
+}
+
+/// `params` and `body` are merged with `-e` overrides, so they must be objects.
+pub(crate) fn object_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({ "type": "object" })
+}
+
+/// A request may name an environment instead of a url, and any field may be
+/// inherited from `defaults` or `extends`, so nothing is required.
+pub(crate) fn request_schema(schema: &mut Schema) {
+    schema.remove("required");
+    if let Some(properties) = schema.get_mut("properties").and_then(|p| p.as_object_mut()) {
+        properties.insert(
+            "env".into(),
+            json!({ "type": "string", "description": "environment whose base_url is prefixed to path" }),
+        );
+        properties.insert(
+            "path".into(),
+            json!({ "type": "string", "description": "path appended to the base_url of env" }),
+        );
+    }
+}
+
+/// Profiles get `req1` and `req2` from their parent or `defaults` too.
+pub(crate) fn profile_schema(schema: &mut Schema) {
+    schema.remove("required");
+}
+
+pub(crate) fn diff_config_schema(schema: &mut Schema) {
+    add_sections(schema, "DiffProfile");
+}
+
+pub(crate) fn request_config_schema(schema: &mut Schema) {
+    add_sections(schema, "RequestProfile");
+}
+
+/// Sections consumed while the config is loaded, so they have no field.
+fn add_sections(schema: &mut Schema, profile: &str) {
+    if let Some(properties) = schema.get_mut("properties").and_then(|p| p.as_object_mut()) {
+        properties.insert(
+            "defaults".into(),
+            json!({
+                "$ref": format!("#/$defs/{}", profile),
+                "description": "fields every profile inherits"
+            }),
+        );
+        properties.insert(
+            "include".into(),
+            json!({
+                "description": "config files or globs merged into this one, relative to it",
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } }
+                ]
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DiffConfig, RequestConfig};
+    use schemars::schema_for;
+    use serde_json::json;
+
+    #[test]
+    fn diff_config_schema_should_describe_raw_config() {
+        let schema = schema_for!(DiffConfig);
+        let schema = schema.as_value();
+
+        assert_eq!(
+            schema["additionalProperties"],
+            json!({"$ref": "#/$defs/DiffProfile"})
+        );
+        for section in ["vars", "environments", "defaults", "include"] {
+            assert!(schema["properties"].get(section).is_some(), "{}", section);
+        }
+
+        let request = &schema["$defs"]["RequestProfile"];
+        assert!(request.get("required").is_none());
+        assert_eq!(request["properties"]["params"]["type"], "object");
+        assert_eq!(request["properties"]["method"]["default"], "GET");
+        assert!(request["properties"].get("env").is_some());
+        assert_eq!(
+            schema["properties"]["vars"]["additionalProperties"]["type"],
+            json!(["string", "number", "boolean"])
+        );
+    }
+
+    #[test]
+    fn request_config_schema_should_use_request_profiles() {
+        let schema = schema_for!(RequestConfig);
+        assert_eq!(
+            schema.as_value()["additionalProperties"],
+            json!({"$ref": "#/$defs/RequestProfile"})
+        );
+    }
+}
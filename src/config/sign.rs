@@ -0,0 +1,391 @@
+use super::Secret;
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Request;
+use ring::{digest, hmac};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+/// Signs a request once it's fully built, nothing may change it afterwards.
+pub trait SignRequest {
+    fn sign(&self, request: &mut Request) -> Result<()>;
+}
+
+/// How a request is signed, picked by `type`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Signer {
+    AwsSigv4(AwsSigV4),
+    HmacSha256(HmacSigner),
+}
+
+impl SignRequest for Signer {
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        match self {
+            Signer::AwsSigv4(signer) => signer.sign(request),
+            Signer::HmacSha256(signer) => signer.sign(request),
+        }
+    }
+}
+
+/// AWS Signature Version 4, signing every header the request carries.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct AwsSigV4 {
+    pub access_key: Secret,
+    pub secret_key: Secret,
+    /// for temporary credentials, sent as `x-amz-security-token`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub session_token: Option<Secret>,
+    pub region: String,
+    pub service: String,
+}
+
+/// HMAC-SHA256 of a canonical string, sent in a header.
+///
+/// The canonical string may use `{method}`, `{host}`, `{path}`, `{query}`,
+/// `{body}`, `{body_sha256}`, `{timestamp}` and `{header:name}`. With
+/// `{timestamp}` the unix time is also sent in `timestamp_header`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct HmacSigner {
+    pub secret: Secret,
+    #[serde(default = "default_signature_header")]
+    pub header: String,
+    #[serde(default = "default_canonical")]
+    pub canonical: String,
+    #[serde(default = "default_timestamp_header")]
+    pub timestamp_header: String,
+    #[serde(default)]
+    pub encoding: SignatureEncoding,
+    /// put in front of the signature, e.g. `HMAC-SHA256 `
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+fn default_signature_header() -> String {
+    "x-signature".into()
+}
+
+fn default_canonical() -> String {
+    "{method}\n{path}\n{query}\n{timestamp}\n{body_sha256}".into()
+}
+
+fn default_timestamp_header() -> String {
+    "x-timestamp".into()
+}
+
+impl SignRequest for AwsSigV4 {
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        self.sign_at(request, OffsetDateTime::now_utc())
+    }
+}
+
+impl AwsSigV4 {
+    fn sign_at(&self, request: &mut Request, now: OffsetDateTime) -> Result<()> {
+        let amz_date = now.format(format_description!(
+            "[year][month][day]T[hour][minute][second]Z"
+        ))?;
+        let date = &amz_date[..8];
+        let payload_hash = hex(digest::digest(&digest::SHA256, body(request)).as_ref());
+
+        let headers = request.headers_mut();
+        headers.insert("x-amz-date", HeaderValue::from_str(&amz_date)?);
+        // only S3 wants the payload hash as a header
+        if self.service == "s3" {
+            headers.insert(
+                "x-amz-content-sha256",
+                HeaderValue::from_str(&payload_hash)?,
+            );
+        }
+        if let Some(token) = &self.session_token {
+            headers.insert(
+                "x-amz-security-token",
+                HeaderValue::from_str(&token.resolve()?)?,
+            );
+        }
+
+        let mut canonical_headers = BTreeMap::new();
+        canonical_headers.insert("host".to_string(), host(request)?);
+        for name in request.headers().keys() {
+            let values: Vec<_> = request
+                .headers()
+                .get_all(name)
+                .iter()
+                .map(|v| Ok(v.to_str()?.trim().to_string()))
+                .collect::<Result<_>>()?;
+            canonical_headers.insert(name.as_str().to_string(), values.join(","));
+        }
+        let signed_headers = canonical_headers
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let url = request.url();
+        // every service but S3 expects the already encoded path encoded again
+        let path = match self.service.as_str() {
+            "s3" => url.path().to_string(),
+            _ => uri_encode(url.path(), false),
+        };
+        let mut query: Vec<_> = url
+            .query_pairs()
+            .map(|(k, v)| (uri_encode(&k, true), uri_encode(&v, true)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            request.method(),
+            path,
+            query,
+            canonical_headers
+                .iter()
+                .map(|(k, v)| format!("{}:{}\n", k, v))
+                .collect::<String>(),
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+        );
+
+        let mut key = format!("AWS4{}", self.secret_key.resolve()?).into_bytes();
+        for part in [date, &self.region, &self.service, "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key.resolve()?,
+            scope,
+            signed_headers,
+            signature
+        );
+        request
+            .headers_mut()
+            .insert("authorization", sensitive(&authorization)?);
+        Ok(())
+    }
+}
+
+impl SignRequest for HmacSigner {
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        self.sign_at(request, OffsetDateTime::now_utc().unix_timestamp())
+    }
+}
+
+impl HmacSigner {
+    fn sign_at(&self, request: &mut Request, timestamp: i64) -> Result<()> {
+        if self.canonical.contains("{timestamp}") {
+            request.headers_mut().insert(
+                HeaderName::from_str(&self.timestamp_header)?,
+                HeaderValue::from(timestamp),
+            );
+        }
+        let canonical = self.canonical_string(request, timestamp)?;
+        let mac = hmac_sha256(self.secret.resolve()?.as_bytes(), canonical.as_bytes());
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => hex(&mac),
+            SignatureEncoding::Base64 => STANDARD.encode(mac),
+        };
+        let value = format!(
+            "{}{}",
+            self.prefix.as_deref().unwrap_or_default(),
+            signature
+        );
+        request
+            .headers_mut()
+            .insert(HeaderName::from_str(&self.header)?, sensitive(&value)?);
+        Ok(())
+    }
+
+    fn canonical_string(&self, request: &Request, timestamp: i64) -> Result<String> {
+        let mut output = String::new();
+        let mut rest = self.canonical.as_str();
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| anyhow!("unclosed {{ in canonical {:?}", self.canonical))?;
+            let placeholder = &rest[start + 1..start + end];
+            let value = match placeholder {
+                "method" => request.method().to_string(),
+                "host" => host(request)?,
+                "path" => request.url().path().to_string(),
+                "query" => request.url().query().unwrap_or_default().to_string(),
+                "body" => String::from_utf8_lossy(body(request)).into_owned(),
+                "body_sha256" => hex(digest::digest(&digest::SHA256, body(request)).as_ref()),
+                "timestamp" => timestamp.to_string(),
+                _ => match placeholder.strip_prefix("header:") {
+                    Some(name) => request
+                        .headers()
+                        .get(name)
+                        .map(|v| v.to_str())
+                        .transpose()?
+                        .unwrap_or_default()
+                        .to_string(),
+                    None => return Err(anyhow!("unknown placeholder {{{}}}", placeholder)),
+                },
+            };
+            output.push_str(&value);
+            rest = &rest[start + end + 1..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+}
+
+fn body(request: &Request) -> &[u8] {
+    request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .unwrap_or_default()
+}
+
+/// Host with the port when it isn't the default one of the scheme, like
+/// the `Host` header sent along.
+fn host(request: &Request) -> Result<String> {
+    let url = request.url();
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("url {} has no host", url))?;
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Percent-encode everything but unreserved characters, and `/` unless
+/// `slash` is set.
+pub(crate) fn uri_encode(text: &str, slash: bool) -> String {
+    let mut output = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                output.push(byte as char)
+            }
+            b'/' if !slash => output.push('/'),
+            _ => output.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    output
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+pub(super) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sensitive(value: &str) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(value)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{Client, Method};
+    use time::macros::datetime;
+
+    fn request(method: Method, url: &str, body: &str) -> Request {
+        Client::new()
+            .request(method, url)
+            .body(body.to_string())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn aws_sigv4_should_match_the_aws_test_suite() {
+        // get-vanilla and get-vanilla-query-order-key-case of the SigV4 test suite
+        let signer: AwsSigV4 = serde_yaml::from_str(
+            "access_key: AKIDEXAMPLE\nsecret_key: wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY\nregion: us-east-1\nservice: service\n",
+        )
+        .unwrap();
+        let now = datetime!(2015-08-30 12:36:00 UTC);
+        let cases = [
+            (
+                "https://example.amazonaws.com/",
+                "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+            ),
+            (
+                "https://example.amazonaws.com/?Param2=value2&Param1=value1",
+                "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500",
+            ),
+        ];
+
+        for (url, signature) in cases {
+            let mut req = request(Method::GET, url, "");
+            signer.sign_at(&mut req, now).unwrap();
+            assert_eq!(req.headers()["x-amz-date"], "20150830T123600Z");
+            assert_eq!(
+                req.headers()["authorization"],
+                format!(
+                    "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature={}",
+                    signature
+                )
+                .as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn hmac_signer_should_sign_the_canonical_string() {
+        let signer: HmacSigner = serde_yaml::from_str("secret: key\n").unwrap();
+        let req = request(Method::POST, "http://localhost:8080/todos?a=1", "{}");
+        assert_eq!(
+            signer.canonical_string(&req, 1700000000).unwrap(),
+            "POST\n/todos\na=1\n1700000000\n44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+
+        let signer = HmacSigner {
+            canonical: "The quick brown fox jumps over the {header:x-animal}".into(),
+            prefix: Some("HMAC ".into()),
+            ..signer
+        };
+        let mut req = request(Method::GET, "http://localhost/", "");
+        req.headers_mut()
+            .insert("x-animal", HeaderValue::from_static("lazy dog"));
+        signer.sign_at(&mut req, 0).unwrap();
+        assert_eq!(
+            req.headers()["x-signature"],
+            "HMAC f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert!(req.headers().get("x-timestamp").is_none());
+
+        let signer = HmacSigner {
+            canonical: "{verb}".into(),
+            ..signer
+        };
+        let err = signer.sign_at(&mut req, 0).unwrap_err();
+        assert_eq!(err.to_string(), "unknown placeholder {verb}");
+    }
+}
@@ -1,34 +1,136 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::{utils::diff_text, ExtraArgs, RequestProfile, ResponseProfile};
+use super::{
+    check_diff_profile, check_extends, diff_config_schema, lint_files, load_files, profile_schema,
+    resolve_template, run_setup, select_profiles, Environments, LintReport, LoadOptions,
+    ProfileFilter, Resolved, Tagged,
+};
+use crate::{
+    cli::DiffPart,
+    diff::JsonComparator,
+    patch::{diff_patch, PatchOp},
+    utils::{diff_json_text, diff_text, diff_text_side_by_side},
+    ExtraArgs, FilteredResponse, JsonChange, RequestProfile, ResponseBody, ResponseProfile,
+};
 use anyhow::{Context, Result};
+use reqwest::Client;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tokio::fs;
+use serde_yaml::Value;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[schemars(transform = diff_config_schema)]
 pub struct DiffConfig {
+    /// values for `{{ var }}` placeholders in the profiles, so no profile can
+    /// be named `vars`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    #[schemars(schema_with = "super::vars_schema")]
+    pub vars: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub environments: Environments,
     #[serde(flatten)]
     pub profiles: HashMap<String, DiffProfile>,
 }
 
 impl DiffConfig {
     pub fn new(profiles: HashMap<String, DiffProfile>) -> Self {
-        Self { profiles }
+        Self {
+            vars: BTreeMap::new(),
+            environments: Environments::default(),
+            profiles,
+        }
     }
 
     pub async fn load_yaml(path: &str) -> anyhow::Result<Self> {
-        let content = fs::read_to_string(path).await?;
-        Self::from_yaml(&content)
+        Self::load_yaml_with(path, &LoadOptions::default()).await
+    }
+
+    pub async fn load_yaml_with(path: &str, options: &LoadOptions) -> anyhow::Result<Self> {
+        Self::load_yaml_files(&[path.to_string()], options).await
+    }
+
+    /// Load and merge several config files along with their includes.
+    pub async fn load_yaml_files(paths: &[String], options: &LoadOptions) -> anyhow::Result<Self> {
+        let value = load_files(paths).await?;
+        Self::from_value_with(value, options)
+    }
+
+    /// Check every profile of config files, collecting all their problems.
+    pub async fn lint_files(paths: &[String], options: &LoadOptions) -> LintReport {
+        lint_files(paths, options, Self::resolve_value, check_diff_profile).await
     }
+
     pub fn from_yaml(context: &str) -> anyhow::Result<Self> {
-        Ok(serde_yaml::from_str(context)?)
+        Self::from_yaml_with(context, &LoadOptions::default())
+    }
+
+    pub fn from_yaml_with(context: &str, options: &LoadOptions) -> anyhow::Result<Self> {
+        Self::from_value_with(serde_yaml::from_str(context)?, options)
+    }
+
+    pub(crate) fn from_value_with(
+        value: serde_yaml::Value,
+        options: &LoadOptions,
+    ) -> anyhow::Result<Self> {
+        let value = Self::resolve_value(value, options)?.into_config()?;
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    /// Resolve templates, inheritance and environments of a raw config.
+    pub(crate) fn resolve_value(
+        value: serde_yaml::Value,
+        options: &LoadOptions,
+    ) -> anyhow::Result<Resolved> {
+        resolve_template(value, options, |envs, profile| {
+            // setup steps go to the environment of the side they run for, a
+            // shared list is split when the sides are sent to different ones
+            if let Some(setup) = profile.remove("setup") {
+                let (env1, env2) = (options.env1.as_deref(), options.env2.as_deref());
+                let setup = match setup {
+                    Value::Mapping(mut sides) => {
+                        for (side, target) in [("req1", env1), ("req2", env2)] {
+                            let steps = sides.remove(side).unwrap_or_default();
+                            let steps = resolve_setup(envs, steps, target)
+                                .with_context(|| format!("setup.{}", side))?;
+                            sides.insert(side.into(), steps);
+                        }
+                        Value::Mapping(sides)
+                    }
+                    steps => {
+                        let steps1 = resolve_setup(envs, steps.clone(), env1).context("setup")?;
+                        let steps2 = resolve_setup(envs, steps, env2).context("setup")?;
+                        match steps1 == steps2 {
+                            true => steps1,
+                            false => Value::Mapping(
+                                [("req1".into(), steps1), ("req2".into(), steps2)]
+                                    .into_iter()
+                                    .collect(),
+                            ),
+                        }
+                    }
+                };
+                profile.insert("setup".into(), setup);
+            }
+            envs.resolve_in(profile, "req1", options.env1.as_deref())?;
+            envs.resolve_in(profile, "req2", options.env2.as_deref())
+        })
     }
 
     pub fn get_profile(&self, name: &str) -> Option<&DiffProfile> {
         self.profiles.get(name)
     }
 
+    pub fn select_profiles(&self, filter: &ProfileFilter) -> Result<Vec<(&str, &DiffProfile)>> {
+        select_profiles(&self.profiles, filter)
+    }
+
     pub fn validate(&self) -> Result<()> {
+        let parents = self
+            .profiles
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile.extends.as_deref()))
+            .collect();
+        check_extends(&parents)?;
         for (name, profile) in &self.profiles {
             profile
                 .validate()
@@ -38,33 +140,338 @@ impl DiffConfig {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Setup steps with the `env` they name, or `target` in its place.
+fn resolve_setup(envs: &Environments, mut steps: Value, target: Option<&str>) -> Result<Value> {
+    if let Value::Sequence(steps) = &mut steps {
+        for (i, step) in steps.iter_mut().enumerate() {
+            if let Value::Mapping(step) = step {
+                envs.resolve(step, target)
+                    .with_context(|| format!("[{}]", i))?;
+            }
+        }
+    }
+    Ok(steps)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[schemars(transform = profile_schema)]
 pub struct DiffProfile {
+    /// profile whose fields this one inherits, resolved when the config is loaded
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub extends: Option<String>,
+    /// requests sent in order before req1 and req2, whose `extract`ed values
+    /// fill `{{ name }}` placeholders of the later requests
+    #[serde(skip_serializing_if = "Setup::is_empty", default)]
+    pub setup: Setup,
     pub req1: RequestProfile,
     pub req2: RequestProfile,
     #[serde(skip_serializing_if = "is_default", default)]
     pub res: ResponseProfile,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
+}
+
+/// Setup steps of a profile, the same for both sides or a list for each.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum Setup {
+    Shared(Vec<RequestProfile>),
+    PerSide {
+        #[serde(default)]
+        req1: Vec<RequestProfile>,
+        #[serde(default)]
+        req2: Vec<RequestProfile>,
+    },
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Setup::Shared(vec![])
+    }
+}
+
+impl Setup {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Setup::Shared(steps) => steps.is_empty(),
+            Setup::PerSide { req1, req2 } => req1.is_empty() && req2.is_empty(),
+        }
+    }
+
+    /// Run the steps, returning the values extracted for req1 and for req2.
+    async fn run(
+        &self,
+        client: &Client,
+    ) -> Result<(BTreeMap<String, String>, BTreeMap<String, String>)> {
+        match self {
+            Setup::Shared(steps) => {
+                let values = run_setup(client, steps).await?;
+                Ok((values.clone(), values))
+            }
+            Setup::PerSide { req1, req2 } => tokio::try_join!(
+                async { run_setup(client, req1).await.context("req1") },
+                async { run_setup(client, req2).await.context("req2") },
+            ),
+        }
+    }
+}
+
+/// Both filtered responses of a profile along with their structural changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffResult {
+    pub res1: FilteredResponse,
+    pub res2: FilteredResponse,
+    /// Body changes by path, only available when both bodies are json
+    pub changes: Option<Vec<JsonChange>>,
 }
+
 impl DiffProfile {
     pub async fn diff(&self, args: ExtraArgs) -> Result<String> {
-        let res1 = self.req1.send(&args).await?;
-        let res2 = self.req2.send(&args).await?;
+        self.compare(&Client::new(), &args).await?.to_text()
+    }
+
+    /// Run the setup steps, then send req1 and req2 concurrently and diff
+    /// their filtered responses.
+    pub async fn compare(&self, client: &Client, args: &ExtraArgs) -> Result<DiffResult> {
+        let (values1, values2) = self.setup.run(client).await?;
+        let req1 = self.req1.with_vars(&values1).context("req1")?;
+        let req2 = self.req2.with_vars(&values2).context("req2")?;
+        let (res1, res2) =
+            tokio::try_join!(req1.send_with(client, args), req2.send_with(client, args))?;
+
+        let res1 = res1.filter(&self.res).await?;
+        let res2 = res2.filter(&self.res).await?;
+
+        let changes = match (&res1.body, &res2.body) {
+            (ResponseBody::Json(json1), ResponseBody::Json(json2)) => {
+                Some(JsonComparator::new(&self.res).diff(json1, json2))
+            }
+            _ => None,
+        };
 
-        let text1 = res1.filter_text(&self.res).await?;
-        let text2 = res2.filter_text(&self.res).await?;
-        diff_text(&text1, &text2)
+        Ok(DiffResult {
+            res1,
+            res2,
+            changes,
+        })
     }
 
     pub fn validate(&self) -> Result<()> {
+        let sides = match &self.setup {
+            Setup::Shared(steps) => vec![("setup", steps)],
+            Setup::PerSide { req1, req2 } => vec![("req1 setup", req1), ("req2 setup", req2)],
+        };
+        for (label, steps) in sides {
+            for (i, step) in steps.iter().enumerate() {
+                step.validate()
+                    .with_context(|| format!("{} step {} failed to validate ", label, i + 1))?;
+            }
+        }
         self.req1.validate().context("req1 failed to validate ")?;
-        self.req2.validate().context("req1 failed to validate ")?;
+        self.req2.validate().context("req2 failed to validate ")?;
         Ok(())
     }
     pub fn new(req1: RequestProfile, req2: RequestProfile, res: ResponseProfile) -> Self {
-        Self { req1, req2, res }
+        Self {
+            extends: None,
+            setup: Setup::default(),
+            req1,
+            req2,
+            res,
+            tags: vec![],
+        }
+    }
+}
+
+impl Tagged for DiffProfile {
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+impl DiffResult {
+    pub fn status_changed(&self) -> bool {
+        self.res1.status != self.res2.status
+    }
+
+    pub fn headers_changed(&self) -> bool {
+        self.res1.headers != self.res2.headers
+    }
+
+    pub fn body_changed(&self) -> bool {
+        match &self.changes {
+            Some(changes) => !changes.is_empty(),
+            None => self.res1.body != self.res2.body,
+        }
+    }
+
+    /// Whether any of the given parts differ, an empty list means every part.
+    pub fn has_changes(&self, parts: &[DiffPart]) -> bool {
+        let check = |part| parts.is_empty() || parts.contains(&part);
+        (check(DiffPart::Status) && self.status_changed())
+            || (check(DiffPart::Headers) && self.headers_changed())
+            || (check(DiffPart::Body) && self.body_changed())
+    }
+
+    /// JSON Patch turning the body of req1 into the body of req2
+    pub fn to_patch(&self) -> Vec<PatchOp> {
+        diff_patch(&self.res1.body.to_value(), &self.res2.body.to_value())
+    }
+
+    pub fn to_text(&self) -> Result<String> {
+        match &self.changes {
+            Some(changes) => {
+                let mut output = diff_text(&self.res1.head()?, &self.res2.head()?)?;
+                output.push_str(&diff_json_text(changes)?);
+                Ok(output)
+            }
+            None => diff_text(&self.res1.to_text()?, &self.res2.to_text()?),
+        }
+    }
+
+    pub fn to_side_by_side(&self, width: usize) -> Result<String> {
+        diff_text_side_by_side(&self.res1.to_text()?, &self.res2.to_text()?, width)
     }
 }
 
 pub fn is_default<T: Default + PartialEq>(v: &T) -> bool {
     v == &T::default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::{StatusCode, Version};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn response(age: &'static str, body: serde_json::Value) -> FilteredResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert("age", HeaderValue::from_static(age));
+        FilteredResponse {
+            url: "http://localhost/todo".parse().unwrap(),
+            version: Version::HTTP_11,
+            status: StatusCode::OK,
+            headers,
+            body: ResponseBody::Json(body),
+            elapsed: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn diff_result_has_changes_should_respect_parts() {
+        let res1 = response("1", json!({"a": 1}));
+        let res2 = response("2", json!({"a": 1}));
+        let result = DiffResult {
+            res1,
+            res2,
+            changes: Some(vec![]),
+        };
+
+        assert!(result.has_changes(&[]));
+        assert!(result.has_changes(&[DiffPart::Headers]));
+        assert!(!result.has_changes(&[DiffPart::Status, DiffPart::Body]));
+    }
+
+    #[test]
+    fn select_profiles_should_filter_by_tags() {
+        let config = DiffConfig::from_yaml(
+            r#"
+todo:
+  req1: { url: "http://localhost/todo/1" }
+  req2: { url: "http://localhost/todo/2" }
+  tags: [smoke]
+users:
+  req1: { url: "http://localhost/users/1" }
+  req2: { url: "http://localhost/users/2" }
+  tags: [smoke, slow]
+posts:
+  req1: { url: "http://localhost/posts/1" }
+  req2: { url: "http://localhost/posts/2" }
+"#,
+        )
+        .unwrap();
+        let names = |filter: ProfileFilter| -> Vec<&str> {
+            config
+                .select_profiles(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect();
+
+        let filter = ProfileFilter {
+            tags: tags(&["smoke"]),
+            ..Default::default()
+        };
+        assert_eq!(names(filter), ["todo", "users"]);
+
+        let filter = ProfileFilter {
+            all: true,
+            exclude_tags: tags(&["slow"]),
+            ..Default::default()
+        };
+        assert_eq!(names(filter), ["posts", "todo"]);
+
+        let filter = ProfileFilter {
+            patterns: vec!["u*".into()],
+            tags: tags(&["smoke"]),
+            exclude_tags: tags(&["slow"]),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.select_profiles(&filter).unwrap_err().to_string(),
+            "No profile matches --profile u* --tag smoke --exclude-tag slow"
+        );
+
+        let filter = ProfileFilter {
+            patterns: vec!["todo".into(), "users".into()],
+            exclude_tags: tags(&["smoke"]),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.select_profiles(&filter).unwrap_err().to_string(),
+            "No profile matches --profile todo --profile users --exclude-tag smoke"
+        );
+    }
+
+    #[test]
+    fn setup_should_follow_the_environment_of_each_side() {
+        let config = r#"
+environments:
+  staging: { base_url: "http://staging" }
+  prod: { base_url: "http://prod" }
+todo:
+  setup:
+    - env: staging
+      path: /login
+      extract: { token: access_token }
+  req1: { env: staging, path: "/todo", headers: { authorization: "{{ token }}" } }
+  req2: { env: prod, path: "/todo", headers: { authorization: "{{ token }}" } }
+"#;
+        let urls = |steps: &[RequestProfile]| -> Vec<String> {
+            steps.iter().map(|step| step.url.to_string()).collect()
+        };
+
+        let options = LoadOptions::default();
+        let config1 = DiffConfig::from_yaml_with(config, &options).unwrap();
+        let Setup::Shared(steps) = &config1.profiles["todo"].setup else {
+            panic!("setup should be shared");
+        };
+        assert_eq!(urls(steps), ["http://staging/login"]);
+
+        let options = LoadOptions {
+            env1: Some("staging".into()),
+            env2: Some("prod".into()),
+            ..Default::default()
+        };
+        let config2 = DiffConfig::from_yaml_with(config, &options).unwrap();
+        let Setup::PerSide { req1, req2 } = &config2.profiles["todo"].setup else {
+            panic!("setup should be split");
+        };
+        assert_eq!(urls(req1), ["http://staging/login"]);
+        assert_eq!(urls(req2), ["http://prod/login"]);
+    }
+}
@@ -1,22 +1,62 @@
-use super::{LoadConfig, ValidateConfig};
-use crate::RequestProfile;
+use super::{
+    check_request_profile, is_default, lint_files, request_config_schema, resolve_requests,
+    select_profiles, Environments, LintReport, LoadConfig, LoadOptions, ProfileFilter,
+    ValidateConfig,
+};
+use crate::{pattern::glob_match, RequestProfile};
 use anyhow::{Context, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[schemars(transform = request_config_schema)]
 pub struct RequestConfig {
+    /// values for `{{ var }}` placeholders in the profiles, so no profile can
+    /// be named `vars`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    #[schemars(schema_with = "super::vars_schema")]
+    pub vars: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub environments: Environments,
     #[serde(flatten)]
     pub profiles: HashMap<String, RequestProfile>,
 }
 
 impl RequestConfig {
     pub fn new(profiles: HashMap<String, RequestProfile>) -> Self {
-        Self { profiles }
+        Self {
+            vars: BTreeMap::new(),
+            environments: Environments::default(),
+            profiles,
+        }
     }
     pub fn get_profile(&self, name: &str) -> Option<&RequestProfile> {
         self.profiles.get(name)
     }
+
+    pub fn select_profiles(&self, filter: &ProfileFilter) -> Result<Vec<(&str, &RequestProfile)>> {
+        select_profiles(&self.profiles, filter)
+    }
+
+    /// Same profiles as `select_profiles`, ordered as their names or globs
+    /// are given, so each one can use what the ones before it extract.
+    pub fn select_sequence(&self, filter: &ProfileFilter) -> Result<Vec<(&str, &RequestProfile)>> {
+        let mut selected = self.select_profiles(filter)?;
+        selected.sort_by_key(|(name, _)| {
+            filter
+                .patterns
+                .iter()
+                .position(|p| glob_match(p, name))
+                .unwrap_or(usize::MAX)
+        });
+        Ok(selected)
+    }
+
+    /// Check every profile of config files, collecting all their problems.
+    pub async fn lint_files(paths: &[String], options: &LoadOptions) -> LintReport {
+        lint_files(paths, options, resolve_requests, check_request_profile).await
+    }
 }
 
 impl LoadConfig for RequestConfig {}
@@ -0,0 +1,406 @@
+use crate::path::{JsonPath, PathSegment, ValuePath};
+use crate::{config::is_default, ResponseProfile};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// A single semantic difference between two JSON documents.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonChange {
+    Added {
+        path: ValuePath,
+        value: Value,
+    },
+    Removed {
+        path: ValuePath,
+        value: Value,
+    },
+    Changed {
+        path: ValuePath,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl JsonChange {
+    pub fn path(&self) -> &ValuePath {
+        match self {
+            JsonChange::Added { path, .. }
+            | JsonChange::Removed { path, .. }
+            | JsonChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for JsonChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonChange::Added { path, value } => write!(f, "+ {}: {}", path, value),
+            JsonChange::Removed { path, value } => write!(f, "- {}: {}", path, value),
+            JsonChange::Changed { path, old, new } => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+/// How elements of the arrays at `path` are paired up before being compared.
+///
+/// With `key` set, elements are matched by the value of that field and then
+/// diffed field by field. With `unordered` set, or for elements missing the
+/// key, elements are matched with an equal element regardless of position.
+///
+/// Elements matched by key are reported as `[key=value]`, the others at their
+/// index in the old array when removed and in the new one when added.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct ArrayRule {
+    pub path: JsonPath,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub unordered: bool,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub key: Option<String>,
+}
+
+impl ArrayRule {
+    /// Whether elements are paired by their index, as without a rule.
+    pub fn pairs_by_index(&self) -> bool {
+        !self.unordered && self.key.is_none()
+    }
+}
+
+/// How scalar values at `path` are considered equal.
+///
+/// Numbers are compared by value, so `1.0` equals `1`, and may differ by up
+/// to `abs_tolerance` or by `rel_tolerance` times the larger magnitude. With
+/// `coerce` set, numeric strings such as `"42"` are compared as numbers.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct CompareRule {
+    pub path: JsonPath,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub abs_tolerance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rel_tolerance: Option<f64>,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub coerce: bool,
+}
+
+/// Structural comparator configured by the `ResponseProfile` of a diff.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonComparator<'a> {
+    arrays: &'a [ArrayRule],
+    compare: &'a [CompareRule],
+}
+
+/// Walk both documents and report every added, removed or changed value by path.
+///
+/// Object keys are compared regardless of their order, array elements are
+/// compared by index.
+pub fn diff_json(old: &Value, new: &Value) -> Vec<JsonChange> {
+    JsonComparator::default().diff(old, new)
+}
+
+impl<'a> JsonComparator<'a> {
+    pub fn new(profile: &'a ResponseProfile) -> Self {
+        Self {
+            arrays: &profile.arrays,
+            compare: &profile.compare,
+        }
+    }
+
+    pub fn diff(&self, old: &Value, new: &Value) -> Vec<JsonChange> {
+        let mut changes = vec![];
+        self.walk(&mut vec![], old, new, &mut changes);
+        changes
+    }
+
+    fn walk(
+        &self,
+        path: &mut Vec<PathSegment>,
+        old: &Value,
+        new: &Value,
+        changes: &mut Vec<JsonChange>,
+    ) {
+        match (old, new) {
+            (Value::Object(o1), Value::Object(o2)) => {
+                for (k, v1) in o1 {
+                    path.push(PathSegment::Key(k.clone()));
+                    match o2.get(k) {
+                        Some(v2) => self.walk(path, v1, v2, changes),
+                        None => changes.push(JsonChange::Removed {
+                            path: ValuePath(path.clone()),
+                            value: v1.clone(),
+                        }),
+                    }
+                    path.pop();
+                }
+                for (k, v2) in o2.iter().filter(|(k, _)| !o1.contains_key(*k)) {
+                    path.push(PathSegment::Key(k.clone()));
+                    changes.push(JsonChange::Added {
+                        path: ValuePath(path.clone()),
+                        value: v2.clone(),
+                    });
+                    path.pop();
+                }
+            }
+            (Value::Array(a1), Value::Array(a2)) => {
+                match self.arrays.iter().find(|r| r.path.matches(path)) {
+                    Some(rule) if !rule.pairs_by_index() => {
+                        self.walk_paired(rule, path, a1, a2, changes)
+                    }
+                    _ => self.walk_indexed(path, a1, a2, changes),
+                }
+            }
+            _ if !self.is_equivalent(path, old, new) => changes.push(JsonChange::Changed {
+                path: ValuePath(path.clone()),
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    fn walk_indexed(
+        &self,
+        path: &mut Vec<PathSegment>,
+        a1: &[Value],
+        a2: &[Value],
+        changes: &mut Vec<JsonChange>,
+    ) {
+        for idx in 0..a1.len().max(a2.len()) {
+            path.push(PathSegment::Index(idx));
+            match (a1.get(idx), a2.get(idx)) {
+                (Some(v1), Some(v2)) => self.walk(path, v1, v2, changes),
+                (Some(v1), None) => changes.push(JsonChange::Removed {
+                    path: ValuePath(path.clone()),
+                    value: v1.clone(),
+                }),
+                (None, Some(v2)) => changes.push(JsonChange::Added {
+                    path: ValuePath(path.clone()),
+                    value: v2.clone(),
+                }),
+                (None, None) => unreachable!(),
+            }
+            path.pop();
+        }
+    }
+
+    /// Pair old and new elements according to the rule and diff the pairs.
+    /// Elements left without a partner are reported as removed or added.
+    fn walk_paired(
+        &self,
+        rule: &ArrayRule,
+        path: &mut Vec<PathSegment>,
+        a1: &[Value],
+        a2: &[Value],
+        changes: &mut Vec<JsonChange>,
+    ) {
+        let segment = |v: &Value, idx: usize| {
+            let key = rule.key.as_deref();
+            match key.and_then(|key| Some((key, v.get(key)?))) {
+                Some((key, value)) => PathSegment::Keyed {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                },
+                None => PathSegment::Index(idx),
+            }
+        };
+        let mut by_key: HashMap<String, VecDeque<usize>> = HashMap::new();
+        for (j, v2) in a2.iter().enumerate() {
+            if let PathSegment::Keyed { value, .. } = segment(v2, j) {
+                by_key.entry(value).or_default().push_back(j);
+            }
+        }
+        let mut used = vec![false; a2.len()];
+
+        for (i, v1) in a1.iter().enumerate() {
+            path.push(segment(v1, i));
+            let found = match path.last() {
+                Some(PathSegment::Keyed { value, .. }) => {
+                    let queue = by_key.get_mut(value);
+                    let found = queue.and_then(|queue| {
+                        std::iter::from_fn(|| queue.pop_front()).find(|&j| !used[j])
+                    });
+                    if let Some(j) = found {
+                        self.walk(path, v1, &a2[j], changes);
+                    }
+                    found
+                }
+                // an equal element has no changes to report
+                _ => (0..a2.len())
+                    .filter(|&j| !used[j])
+                    .find(|&j| self.diff_at(path, v1, &a2[j]).is_empty()),
+            };
+            match found {
+                Some(j) => used[j] = true,
+                None => changes.push(JsonChange::Removed {
+                    path: ValuePath(path.clone()),
+                    value: v1.clone(),
+                }),
+            }
+            path.pop();
+        }
+
+        for (j, v2) in a2.iter().enumerate().filter(|(j, _)| !used[*j]) {
+            path.push(segment(v2, j));
+            changes.push(JsonChange::Added {
+                path: ValuePath(path.clone()),
+                value: v2.clone(),
+            });
+            path.pop();
+        }
+    }
+
+    fn is_equivalent(&self, path: &[PathSegment], old: &Value, new: &Value) -> bool {
+        match self.compare.iter().find(|r| r.path.matches(path)) {
+            Some(rule) => rule.is_equivalent(old, new),
+            None => old == new,
+        }
+    }
+
+    fn diff_at(&self, path: &mut Vec<PathSegment>, old: &Value, new: &Value) -> Vec<JsonChange> {
+        let mut changes = vec![];
+        self.walk(path, old, new, &mut changes);
+        changes
+    }
+}
+
+impl CompareRule {
+    fn is_equivalent(&self, old: &Value, new: &Value) -> bool {
+        match (self.as_number(old), self.as_number(new)) {
+            (Some(a), Some(b)) => {
+                let delta = (a - b).abs();
+                delta <= self.abs_tolerance.unwrap_or_default()
+                    || delta <= self.rel_tolerance.unwrap_or_default() * a.abs().max(b.abs())
+            }
+            _ => old == new,
+        }
+    }
+
+    fn as_number(&self, value: &Value) -> Option<f64> {
+        match value {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) if self.coerce => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff_json_should_ignore_key_order() {
+        let old = json!({"a": 1, "b": {"c": true, "d": null}});
+        let new = json!({"b": {"d": null, "c": true}, "a": 1});
+        assert!(diff_json(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn diff_json_should_report_changes_by_path() {
+        let old = json!({"items": [{"price": 10}, {"price": 5}], "gone": 1});
+        let new = json!({"items": [{"price": 12}, {"price": 5}, {"price": 7}], "new key": "x"});
+        let changes: Vec<String> = diff_json(&old, &new)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "- $.gone: 1",
+                "~ $.items[0].price: 10 -> 12",
+                "+ $.items[2]: {\"price\":7}",
+                "+ $[\"new key\"]: \"x\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_json_should_report_type_change_as_changed() {
+        let old = json!({"a": [1]});
+        let new = json!({"a": {"0": 1}});
+        assert_eq!(
+            diff_json(&old, &new),
+            vec![JsonChange::Changed {
+                path: ValuePath(vec![PathSegment::Key("a".into())]),
+                old: json!([1]),
+                new: json!({"0": 1}),
+            }]
+        );
+    }
+
+    fn profile(yaml: &str) -> ResponseProfile {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn diff_strings(profile: &ResponseProfile, old: &Value, new: &Value) -> Vec<String> {
+        JsonComparator::new(profile)
+            .diff(old, new)
+            .iter()
+            .map(|c| c.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn unordered_array_should_ignore_element_order() {
+        let profile = profile("arrays: [{path: $, unordered: true}]");
+        let old = json!([1, 2, 2, {"a": 1}]);
+        let new = json!([{"a": 1}, 2, 3, 1]);
+        assert_eq!(
+            diff_strings(&profile, &old, &new),
+            vec!["- $[2]: 2", "+ $[2]: 3"]
+        );
+    }
+
+    #[test]
+    fn keyed_array_should_diff_elements_with_same_key() {
+        let profile = profile("arrays: [{path: 'data.items', key: id}]");
+        let old = json!({"data": {"items": [{"id": 1, "v": "a"}, {"id": 2, "v": "b"}]}});
+        let new = json!({"data": {"items": [{"id": 3, "v": "c"}, {"id": 2, "v": "b"}, {"id": 1, "v": "x"}]}});
+        assert_eq!(
+            diff_strings(&profile, &old, &new),
+            vec![
+                "~ $.data.items[id=1].v: \"a\" -> \"x\"",
+                "+ $.data.items[id=3]: {\"id\":3,\"v\":\"c\"}",
+            ]
+        );
+
+        let new = json!({"data": {"items": [{"id": 1, "v": "a"}, {"v": "d"}]}});
+        assert_eq!(
+            diff_strings(&profile, &old, &new),
+            vec![
+                "- $.data.items[id=2]: {\"id\":2,\"v\":\"b\"}",
+                "+ $.data.items[1]: {\"v\":\"d\"}",
+            ]
+        );
+    }
+
+    #[test]
+    fn compare_rule_should_allow_tolerance_and_coercion() {
+        let profile = profile(
+            "compare: [{path: 'price', abs_tolerance: 0.01}, {path: '**', rel_tolerance: 1e-9, coerce: true}]",
+        );
+        let old = json!({"price": 10.0, "count": "42", "ratio": 0.1234567891, "name": "a", "n": 1});
+        let new =
+            json!({"price": 10.005, "count": 42, "ratio": 0.1234567892, "name": "a", "n": 1.0});
+        assert!(diff_strings(&profile, &old, &new).is_empty());
+
+        let new = json!({"price": 10.02, "count": "42", "ratio": 0.12, "name": "b", "n": 1});
+        assert_eq!(
+            diff_strings(&profile, &old, &new),
+            vec![
+                "~ $.name: \"a\" -> \"b\"",
+                "~ $.price: 10.0 -> 10.02",
+                "~ $.ratio: 0.1234567891 -> 0.12",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_json_without_rules_should_be_strict() {
+        assert_eq!(diff_json(&json!(1), &json!(1.0)).len(), 1);
+        assert_eq!(diff_json(&json!("1"), &json!(1)).len(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{DiffResult, Summary, Verdict};
+use anyhow::Result;
+use similar::{DiffTag, TextDiff};
+use std::fmt::Write as _;
+use std::ops::Range;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+.summary td, .summary th { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+.pass { color: #1a7f37; font-weight: bold; }
+.fail { color: #cf222e; font-weight: bold; }
+.diff { font-family: monospace; font-size: 13px; table-layout: fixed; }
+.diff td { padding: 0 6px; white-space: pre-wrap; word-break: break-all; vertical-align: top; }
+.diff td.ln { width: 3em; color: #888; text-align: right; user-select: none; }
+.diff td.del { background: #ffebe9; }
+.diff td.ins { background: #e6ffec; }
+.diff td.empty { background: #f6f8fa; }
+.diff tr.fold > td { padding: 0; }
+.diff details summary { background: #ddf4ff; color: #555; cursor: pointer; padding: 2px 6px; }
+"#;
+
+/// Render a self-contained html page with a side by side diff per profile.
+///
+/// Unchanged lines more than 3 lines away from a change are folded into
+/// collapsible sections, the same context used by the terminal output.
+pub fn html_report(results: &[(&str, &DiffResult)], summary: &Summary) -> Result<String> {
+    let mut output = String::new();
+    writeln!(
+        output,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>xdiff report</title>\n<style>{}</style>\n</head>\n<body>",
+        STYLE
+    )?;
+
+    writeln!(output, "<h1>xdiff report</h1>")?;
+    writeln!(
+        output,
+        "<p>{} profiles: {} passed, {} failed, {} errors</p>",
+        summary.total, summary.passed, summary.failed, summary.errors
+    )?;
+    writeln!(output, "<table class=\"summary\">")?;
+    writeln!(
+        output,
+        "<tr><th>Profile</th><th>Result</th><th>req1</th><th>req2</th></tr>"
+    )?;
+    for row in &summary.profiles {
+        let result = results.iter().find(|(name, _)| *name == row.name);
+        match (result, &row.error) {
+            (Some((_, result)), _) => writeln!(
+                output,
+                "<tr><td><a href=\"#{0}\">{0}</a></td><td>{1}</td><td>{2} {3}</td><td>{4} {5}</td></tr>",
+                escape(&row.name),
+                verdict(row.verdict),
+                result.res1.status.as_u16(),
+                escape(result.res1.url.as_str()),
+                result.res2.status.as_u16(),
+                escape(result.res2.url.as_str()),
+            )?,
+            (None, error) => writeln!(
+                output,
+                "<tr><td>{}</td><td>{}</td><td colspan=\"2\">{}</td></tr>",
+                escape(&row.name),
+                verdict(row.verdict),
+                escape(error.as_deref().unwrap_or_default()),
+            )?,
+        }
+    }
+    writeln!(output, "</table>")?;
+
+    for (name, result) in results {
+        let verdict = summary
+            .get(name)
+            .map(|row| verdict(row.verdict))
+            .unwrap_or_default();
+        writeln!(output, "<h2 id=\"{0}\">{0} {1}</h2>", escape(name), verdict)?;
+        render_diff(
+            &mut output,
+            &result.res1.to_text()?,
+            &result.res2.to_text()?,
+        )?;
+    }
+
+    writeln!(output, "</body>\n</html>")?;
+    Ok(output)
+}
+
+fn verdict(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Pass => "<span class=\"pass\">PASS</span>",
+        Verdict::Fail => "<span class=\"fail\">FAIL</span>",
+        Verdict::Error => "<span class=\"fail\">ERROR</span>",
+    }
+}
+
+fn render_diff(output: &mut String, text1: &str, text2: &str) -> Result<()> {
+    let diff = TextDiff::from_lines(text1, text2);
+    let old = diff.old_slices();
+    let new = diff.new_slices();
+
+    writeln!(output, "<table class=\"diff\">")?;
+
+    let mut old_pos = 0;
+    let mut new_pos = 0;
+    for group in diff.grouped_ops(3) {
+        let (_, old_range, _) = group[0].as_tag_tuple();
+        render_fold(output, old, new_pos, old_pos..old_range.start)?;
+
+        for op in &group {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let len = old_range.len().max(new_range.len());
+            for i in 0..len {
+                let left = (i < old_range.len()).then(|| old_range.start + i);
+                let right = (i < new_range.len()).then(|| new_range.start + i);
+                let (left_class, right_class) = match tag {
+                    DiffTag::Equal => ("", ""),
+                    _ => ("del", "ins"),
+                };
+                write_row(
+                    output,
+                    left.map(|i| (i, old[i], left_class)),
+                    right.map(|i| (i, new[i], right_class)),
+                )?;
+            }
+            old_pos = old_range.end;
+            new_pos = new_range.end;
+        }
+    }
+    render_fold(output, old, new_pos, old_pos..old.len())?;
+
+    writeln!(output, "</table>")?;
+    Ok(())
+}
+
+/// Unchanged lines are identical on both sides, so they are rendered from
+/// the old text with their line numbers on either side.
+fn render_fold(
+    output: &mut String,
+    old: &[&str],
+    new_start: usize,
+    range: Range<usize>,
+) -> Result<()> {
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        output,
+        "<tr class=\"fold\"><td colspan=\"4\"><details><summary>{} unchanged lines</summary><table class=\"diff\">",
+        range.len()
+    )?;
+    for (n, i) in range.enumerate() {
+        write_row(
+            output,
+            Some((i, old[i], "")),
+            Some((new_start + n, old[i], "")),
+        )?;
+    }
+    writeln!(output, "</table></details></td></tr>")?;
+    Ok(())
+}
+
+fn write_row(
+    output: &mut String,
+    left: Option<(usize, &str, &str)>,
+    right: Option<(usize, &str, &str)>,
+) -> Result<()> {
+    write!(output, "<tr>")?;
+    for side in [left, right] {
+        match side {
+            Some((idx, line, class)) => write!(
+                output,
+                "<td class=\"ln\">{}</td><td class=\"{}\">{}</td>",
+                idx + 1,
+                class,
+                escape(line.trim_end_matches(['\r', '\n']))
+            )?,
+            None => write!(output, "<td class=\"ln\"></td><td class=\"empty\"></td>")?,
+        }
+    }
+    writeln!(output, "</tr>")?;
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_diff_should_fold_unchanged_regions() {
+        let text1: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        let text2 = text1.replace("line 10\n", "line <10>\n");
+
+        let mut output = String::new();
+        render_diff(&mut output, &text1, &text2).unwrap();
+
+        // lines 1-6 and 14-20 are folded, 7-13 are shown around the change
+        assert!(output.contains("<summary>6 unchanged lines</summary>"));
+        assert!(output.contains("<summary>7 unchanged lines</summary>"));
+        assert!(output.contains(
+            "<td class=\"ln\">10</td><td class=\"del\">line 10</td><td class=\"ln\">10</td><td class=\"ins\">line &lt;10&gt;</td>"
+        ));
+        assert_eq!(output.matches("<tr>").count(), 20);
+    }
+}
@@ -1,11 +1,32 @@
 pub mod cli;
 mod config;
+mod diff;
+mod html;
+mod mask;
+mod patch;
+mod path;
+mod pattern;
+mod report;
+mod template;
 mod utils;
 pub use config::{
-    get_body_text, get_header_text, get_status_text, DiffConfig, DiffProfile, LoadConfig,
-    RequestConfig, RequestProfile, ResponseProfile,
+    extract_values, get_body_text, get_header_text, get_status_text, Auth, AwsSigV4, ConfigFormat,
+    Diagnostic, DiffConfig, DiffProfile, DiffResult, Environment, Environments, Extract,
+    FilteredResponse, HmacSigner, KeyLocation, LintReport, LoadConfig, LoadOptions, OAuth2,
+    ProfileFilter, RequestConfig, RequestProfile, ResponseBody, ResponseProfile, Secret, Severity,
+    SignRequest, SignatureEncoding, Signer,
 };
-pub use utils::{highlight_text, process_error_output};
+pub use diff::{diff_json, ArrayRule, CompareRule, JsonChange, JsonComparator};
+pub use html::html_report;
+pub use mask::{MaskRule, ValueMatcher};
+pub use patch::{diff_patch, PatchOp};
+pub use path::{JsonPath, PathSegment, ValuePath};
+pub use pattern::HeaderPattern;
+pub use report::{
+    HeaderChange, ProfileReport, Report, ResponseSummary, Summary, SummaryRow, Verdict,
+};
+pub use template::{parse_env_file, TemplateVars};
+pub use utils::{diff_json_text, highlight_text, process_error_output, split_tags};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ExtraArgs {
@@ -0,0 +1,159 @@
+use crate::{pattern::RegexPattern, JsonPath};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Replace volatile values with a placeholder before diffing.
+///
+/// A rule without `matcher` or `regex` masks every non-null value at `path`.
+/// Values that don't match are left untouched, so a field turning `null` or
+/// changing type still shows up in the diff.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct MaskRule {
+    pub path: JsonPath,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub matcher: Option<ValueMatcher>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub regex: Option<RegexPattern>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub placeholder: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueMatcher {
+    Uuid,
+    Iso8601,
+    /// Unix timestamp in seconds or milliseconds
+    Epoch,
+    Jwt,
+}
+
+impl MaskRule {
+    pub fn apply(&self, json: &mut Value) {
+        let placeholder = Value::String(self.placeholder());
+        self.path.for_each_mut(json, |v| {
+            if self.is_match(v) {
+                *v = placeholder.clone();
+            }
+        });
+    }
+
+    fn placeholder(&self) -> String {
+        match (&self.placeholder, self.matcher) {
+            (Some(p), _) => p.clone(),
+            (None, Some(m)) => format!("<{}>", m.name()),
+            (None, None) => "<masked>".to_string(),
+        }
+    }
+
+    fn is_match(&self, value: &Value) -> bool {
+        if value.is_null() {
+            return false;
+        }
+        if let Some(matcher) = self.matcher {
+            if !matcher.is_match(value) {
+                return false;
+            }
+        }
+        match (&self.regex, value) {
+            (None, _) => true,
+            (Some(re), Value::String(s)) => re.is_match(s),
+            (Some(re), Value::Number(n)) => re.is_match(&n.to_string()),
+            (Some(_), _) => false,
+        }
+    }
+}
+
+impl ValueMatcher {
+    fn name(&self) -> &'static str {
+        match self {
+            ValueMatcher::Uuid => "uuid",
+            ValueMatcher::Iso8601 => "iso8601",
+            ValueMatcher::Epoch => "epoch",
+            ValueMatcher::Jwt => "jwt",
+        }
+    }
+
+    fn is_match(&self, value: &Value) -> bool {
+        static UUID: OnceLock<Regex> = OnceLock::new();
+        static ISO8601: OnceLock<Regex> = OnceLock::new();
+        static JWT: OnceLock<Regex> = OnceLock::new();
+
+        let text = match (self, value) {
+            (ValueMatcher::Epoch, Value::Number(n)) => return is_epoch(&n.to_string()),
+            (ValueMatcher::Epoch, Value::String(s)) => return is_epoch(s),
+            (_, Value::String(s)) => s,
+            _ => return false,
+        };
+
+        let regex = match self {
+            ValueMatcher::Uuid => UUID.get_or_init(|| {
+                Regex::new(r"^(?i)[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$")
+                    .unwrap()
+            }),
+            ValueMatcher::Iso8601 => ISO8601.get_or_init(|| {
+                Regex::new(
+                    r"^\d{4}-\d{2}-\d{2}([T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?)?$",
+                )
+                .unwrap()
+            }),
+            ValueMatcher::Jwt => JWT.get_or_init(|| {
+                Regex::new(r"^eyJ[A-Za-z0-9_-]*\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*$").unwrap()
+            }),
+            ValueMatcher::Epoch => unreachable!(),
+        };
+        regex.is_match(text)
+    }
+}
+
+// 10 digits of seconds or 13 digits of milliseconds covers 2001 to 2286
+fn is_epoch(s: &str) -> bool {
+    (s.len() == 10 || s.len() == 13) && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(yaml: &str) -> MaskRule {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn mask_rule_should_replace_matching_values() {
+        let mut json = json!({
+            "items": [
+                {"id": "9b2f6c1e-3f0a-4b8e-9a53-2c1d7e4f8a10", "at": "2022-10-01T12:00:00Z"},
+                {"id": null, "at": 1664625600}
+            ],
+            "token": "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.c2ln"
+        });
+
+        rule("{path: 'items[*].id', matcher: uuid}").apply(&mut json);
+        rule("{path: '**.at', matcher: iso8601}").apply(&mut json);
+        rule("{path: '**.at', matcher: epoch, placeholder: '<time>'}").apply(&mut json);
+        rule("{path: token, matcher: jwt}").apply(&mut json);
+
+        assert_eq!(
+            json,
+            json!({
+                "items": [{"id": "<uuid>", "at": "<iso8601>"}, {"id": null, "at": "<time>"}],
+                "token": "<jwt>"
+            })
+        );
+    }
+
+    #[test]
+    fn mask_rule_with_regex_should_keep_other_values() {
+        let mut json = json!({"a": "tok_123", "b": "other", "c": 42});
+        rule("{path: '*', regex: '^tok_'}").apply(&mut json);
+        assert_eq!(json, json!({"a": "<masked>", "b": "other", "c": 42}));
+
+        rule("{path: c}").apply(&mut json);
+        assert_eq!(json["c"], json!("<masked>"));
+    }
+}
@@ -0,0 +1,174 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// One RFC 6902 operation, paths are JSON pointers.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+}
+
+/// Build a JSON Patch document that turns `old` into `new`.
+///
+/// Renamed object keys with an unchanged value become a `move`, array
+/// elements are compared by index.
+pub fn diff_patch(old: &Value, new: &Value) -> Vec<PatchOp> {
+    let mut ops = vec![];
+    walk("", old, new, &mut ops);
+    ops
+}
+
+fn walk(pointer: &str, old: &Value, new: &Value, ops: &mut Vec<PatchOp>) {
+    match (old, new) {
+        (Value::Object(o1), Value::Object(o2)) => walk_object(pointer, o1, o2, ops),
+        (Value::Array(a1), Value::Array(a2)) => {
+            let common = a1.len().min(a2.len());
+            for idx in 0..common {
+                walk(&child(pointer, &idx.to_string()), &a1[idx], &a2[idx], ops);
+            }
+            // remove from the back so the remaining indices stay valid
+            for idx in (common..a1.len()).rev() {
+                ops.push(PatchOp::Remove {
+                    path: child(pointer, &idx.to_string()),
+                });
+            }
+            for (idx, value) in a2.iter().enumerate().skip(common) {
+                ops.push(PatchOp::Add {
+                    path: child(pointer, &idx.to_string()),
+                    value: value.clone(),
+                });
+            }
+        }
+        _ if old != new => ops.push(PatchOp::Replace {
+            path: pointer.to_string(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn walk_object(
+    pointer: &str,
+    o1: &Map<String, Value>,
+    o2: &Map<String, Value>,
+    ops: &mut Vec<PatchOp>,
+) {
+    let mut added: Vec<(&String, &Value)> =
+        o2.iter().filter(|(k, _)| !o1.contains_key(*k)).collect();
+
+    for (k, v1) in o1 {
+        match o2.get(k) {
+            Some(v2) => walk(&child(pointer, k), v1, v2, ops),
+            None => match added.iter().position(|(_, v2)| *v2 == v1) {
+                Some(pos) => {
+                    let (new_key, _) = added.remove(pos);
+                    ops.push(PatchOp::Move {
+                        from: child(pointer, k),
+                        path: child(pointer, new_key),
+                    });
+                }
+                None => ops.push(PatchOp::Remove {
+                    path: child(pointer, k),
+                }),
+            },
+        }
+    }
+
+    for (k, v2) in added {
+        ops.push(PatchOp::Add {
+            path: child(pointer, k),
+            value: v2.clone(),
+        });
+    }
+}
+
+fn child(pointer: &str, token: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        token.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Minimal RFC 6902 applier covering the operations `diff_patch` emits.
+    fn apply(doc: &mut Value, ops: &[PatchOp]) {
+        fn parent<'a>(doc: &'a mut Value, pointer: &str) -> (&'a mut Value, String) {
+            let (parent, last) = pointer.rsplit_once('/').unwrap();
+            let token = last.replace("~1", "/").replace("~0", "~");
+            (doc.pointer_mut(parent).unwrap(), token)
+        }
+        fn remove(doc: &mut Value, pointer: &str) -> Value {
+            match parent(doc, pointer) {
+                (Value::Object(obj), k) => obj.remove(&k).unwrap(),
+                (Value::Array(arr), i) => arr.remove(i.parse().unwrap()),
+                _ => unreachable!(),
+            }
+        }
+        fn add(doc: &mut Value, pointer: &str, value: Value) {
+            match parent(doc, pointer) {
+                (Value::Object(obj), k) => {
+                    obj.insert(k, value);
+                }
+                (Value::Array(arr), i) => arr.insert(i.parse().unwrap(), value),
+                _ => unreachable!(),
+            }
+        }
+
+        for op in ops {
+            match op {
+                PatchOp::Add { path, value } => add(doc, path, value.clone()),
+                PatchOp::Remove { path } => {
+                    remove(doc, path);
+                }
+                PatchOp::Replace { path, value } => *doc.pointer_mut(path).unwrap() = value.clone(),
+                PatchOp::Move { from, path } => {
+                    let value = remove(doc, from);
+                    add(doc, path, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn diff_patch_should_transform_old_into_new() {
+        let old = json!({"a": 1, "b": {"c": [1, 2, 3]}, "x/y": "moved", "gone": true});
+        let new = json!({"a": 2, "b": {"c": [1, 5], "d": null}, "z~": "moved"});
+
+        let ops = diff_patch(&old, &new);
+        assert_eq!(
+            serde_json::to_value(&ops).unwrap(),
+            json!([
+                {"op": "replace", "path": "/a", "value": 2},
+                {"op": "replace", "path": "/b/c/1", "value": 5},
+                {"op": "remove", "path": "/b/c/2"},
+                {"op": "add", "path": "/b/d", "value": null},
+                {"op": "remove", "path": "/gone"},
+                {"op": "move", "from": "/x~1y", "path": "/z~0"},
+            ])
+        );
+
+        let mut doc = old.clone();
+        apply(&mut doc, &ops);
+        assert_eq!(doc, new);
+    }
+
+    #[test]
+    fn diff_patch_should_replace_root_on_type_change() {
+        let ops = diff_patch(&json!([1]), &json!("text"));
+        assert_eq!(
+            ops,
+            vec![PatchOp::Replace {
+                path: "".into(),
+                value: json!("text")
+            }]
+        );
+    }
+}
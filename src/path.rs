@@ -0,0 +1,393 @@
+use anyhow::{anyhow, Result};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// One step from a JSON value to one of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+    /// Element of an array paired by the json text `value` of its `key` field
+    Keyed {
+        key: String,
+        value: String,
+    },
+}
+
+/// Location of a value inside a JSON document, rendered as `$.items[3].price`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValuePath(pub Vec<PathSegment>);
+
+/// A pattern selecting values inside a JSON document.
+///
+/// Accepts dotted paths (`data.items[*].updated_at`, optionally prefixed with
+/// `$`) and JSON pointers (`/data/items/*/updated_at`). `*` matches any single
+/// key or index and `**` (or `..` in dotted paths) matches any depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    raw: String,
+    segments: Vec<PatternSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternSegment {
+    /// Object key, or array index when it is numeric
+    Name(String),
+    Index(usize),
+    Any,
+    AnyDepth,
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathSegment::Index(idx) => write!(f, "[{}]", idx),
+            PathSegment::Key(key) if is_identifier(key) => write!(f, ".{}", key),
+            PathSegment::Key(key) => write!(f, "[{:?}]", key),
+            PathSegment::Keyed { key, value } => write!(f, "[{}={}]", key, value),
+        }
+    }
+}
+
+impl fmt::Display for ValuePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.0 {
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for ValuePath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl JsonPath {
+    pub fn matches(&self, path: &[PathSegment]) -> bool {
+        match_segments(&self.segments, path)
+    }
+
+    /// Remove every value matched by this path from the document.
+    pub fn remove(&self, value: &mut Value) {
+        remove_matching(self, &mut vec![], value);
+    }
+
+    /// Call `f` on every value matched by this path, outermost first.
+    pub fn for_each_mut(&self, value: &mut Value, mut f: impl FnMut(&mut Value)) {
+        visit_matching(self, &mut vec![], value, &mut f);
+    }
+}
+
+impl PatternSegment {
+    fn matches(&self, segment: &PathSegment) -> bool {
+        match (self, segment) {
+            (PatternSegment::Any | PatternSegment::AnyDepth, _) => true,
+            (PatternSegment::Name(name), PathSegment::Key(key)) => name == key,
+            (PatternSegment::Name(name), PathSegment::Index(idx)) => *name == idx.to_string(),
+            (PatternSegment::Index(i), PathSegment::Index(idx)) => i == idx,
+            (PatternSegment::Index(_), PathSegment::Key(_)) => false,
+            (PatternSegment::Name(_) | PatternSegment::Index(_), PathSegment::Keyed { .. }) => {
+                false
+            }
+        }
+    }
+}
+
+fn match_segments(pattern: &[PatternSegment], path: &[PathSegment]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((PatternSegment::AnyDepth, rest)) => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((segment, rest)) => match path.split_first() {
+            Some((first, tail)) => segment.matches(first) && match_segments(rest, tail),
+            None => false,
+        },
+    }
+}
+
+fn remove_matching(pattern: &JsonPath, path: &mut Vec<PathSegment>, value: &mut Value) {
+    match value {
+        Value::Object(obj) => {
+            let keys: Vec<String> = obj.keys().cloned().collect();
+            for k in keys {
+                path.push(PathSegment::Key(k.clone()));
+                if pattern.matches(path) {
+                    obj.remove(&k);
+                } else if let Some(v) = obj.get_mut(&k) {
+                    remove_matching(pattern, path, v);
+                }
+                path.pop();
+            }
+        }
+        Value::Array(arr) => {
+            let mut idx = 0;
+            arr.retain_mut(|v| {
+                path.push(PathSegment::Index(idx));
+                let matched = pattern.matches(path);
+                if !matched {
+                    remove_matching(pattern, path, v);
+                }
+                path.pop();
+                idx += 1;
+                !matched
+            });
+        }
+        _ => {}
+    }
+}
+
+fn visit_matching(
+    pattern: &JsonPath,
+    path: &mut Vec<PathSegment>,
+    value: &mut Value,
+    f: &mut dyn FnMut(&mut Value),
+) {
+    if pattern.matches(path) {
+        f(value);
+    }
+
+    match value {
+        Value::Object(obj) => {
+            for (k, v) in obj.iter_mut() {
+                path.push(PathSegment::Key(k.clone()));
+                visit_matching(pattern, path, v, f);
+                path.pop();
+            }
+        }
+        Value::Array(arr) => {
+            for (idx, v) in arr.iter_mut().enumerate() {
+                path.push(PathSegment::Index(idx));
+                visit_matching(pattern, path, v, f);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(anyhow!("path must not be empty"));
+        }
+
+        // `$` on its own selects the whole document
+        let segments = if s.starts_with('/') {
+            parse_pointer(s)
+        } else {
+            parse_dotted(s)?
+        };
+
+        Ok(Self {
+            raw: s.to_string(),
+            segments,
+        })
+    }
+}
+
+fn parse_pointer(s: &str) -> Vec<PatternSegment> {
+    s.split('/')
+        .skip(1)
+        .map(|token| match token {
+            "*" => PatternSegment::Any,
+            "**" => PatternSegment::AnyDepth,
+            _ => PatternSegment::Name(token.replace("~1", "/").replace("~0", "~")),
+        })
+        .collect()
+}
+
+fn parse_dotted(s: &str) -> Result<Vec<PatternSegment>> {
+    let rest = s.strip_prefix('$').unwrap_or(s);
+    let mut chars = rest.chars().peekable();
+    let mut segments = vec![];
+
+    // `$.a` and `a` are the same path, a leading `.` only matters as part of `..`
+    if rest.starts_with('.') && !rest.starts_with("..") {
+        chars.next();
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' if chars.peek() == Some(&'.') => {
+                chars.next();
+                segments.push(PatternSegment::AnyDepth);
+            }
+            '.' => {}
+            '[' => {
+                let mut inner = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    inner.push(c);
+                }
+                if !closed {
+                    return Err(anyhow!("unclosed [ in path {:?}", s));
+                }
+                segments.push(parse_bracket(s, inner.trim())?);
+            }
+            _ => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c == '.' || c == '[' {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                segments.push(match name.as_str() {
+                    "*" => PatternSegment::Any,
+                    "**" => PatternSegment::AnyDepth,
+                    _ => PatternSegment::Name(name),
+                });
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+fn parse_bracket(path: &str, inner: &str) -> Result<PatternSegment> {
+    if inner == "*" {
+        return Ok(PatternSegment::Any);
+    }
+    if let Ok(idx) = inner.parse() {
+        return Ok(PatternSegment::Index(idx));
+    }
+    let quoted = (inner.starts_with('"') && inner.ends_with('"'))
+        || (inner.starts_with('\'') && inner.ends_with('\''));
+    if quoted && inner.len() >= 2 {
+        return Ok(PatternSegment::Name(inner[1..inner.len() - 1].to_string()));
+    }
+    Err(anyhow!("invalid segment [{}] in path {:?}", inner, path))
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Serialize for JsonPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for JsonPath {
+    fn schema_name() -> Cow<'static, str> {
+        "JsonPath".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "dotted path such as `items[*].id` or `**.updated_at`, or a JSON pointer",
+            "minLength": 1
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_path_should_parse_dotted_and_pointer_forms() {
+        let expected = vec![
+            PatternSegment::Name("data".into()),
+            PatternSegment::Name("items".into()),
+            PatternSegment::Any,
+            PatternSegment::Name("updated_at".into()),
+        ];
+        for s in [
+            "data.items[*].updated_at",
+            "$.data.items.*.updated_at",
+            "/data/items/*/updated_at",
+        ] {
+            let path: JsonPath = s.parse().unwrap();
+            assert_eq!(path.segments, expected, "{}", s);
+        }
+
+        let path: JsonPath = "$..['a b'][2]".parse().unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                PatternSegment::AnyDepth,
+                PatternSegment::Name("a b".into()),
+                PatternSegment::Index(2),
+            ]
+        );
+        assert!("a[oops]".parse::<JsonPath>().is_err());
+        assert!("a[0".parse::<JsonPath>().is_err());
+        assert!("a['b".parse::<JsonPath>().is_err());
+        assert!("".parse::<JsonPath>().is_err());
+        assert!("$".parse::<JsonPath>().unwrap().matches(&[]));
+    }
+
+    #[test]
+    fn json_path_remove_should_reach_nested_values() {
+        let mut value = json!({
+            "id": 1,
+            "data": {
+                "items": [
+                    {"id": 1, "updated_at": "x"},
+                    {"id": 2, "updated_at": "y", "meta": {"updated_at": "z"}}
+                ]
+            }
+        });
+
+        let path: JsonPath = "data.items[*].updated_at".parse().unwrap();
+        path.remove(&mut value);
+        assert_eq!(value["data"]["items"][0], json!({"id": 1}));
+        assert_eq!(
+            value["data"]["items"][1]["meta"],
+            json!({"updated_at": "z"})
+        );
+
+        let path: JsonPath = "**.updated_at".parse().unwrap();
+        path.remove(&mut value);
+        assert_eq!(value["data"]["items"][1], json!({"id": 2, "meta": {}}));
+
+        let path: JsonPath = "/data/items/0".parse().unwrap();
+        path.remove(&mut value);
+        assert_eq!(
+            value,
+            json!({"id": 1, "data": {"items": [{"id": 2, "meta": {}}]}})
+        );
+    }
+
+    #[test]
+    fn json_path_plain_key_should_only_match_top_level() {
+        let mut value = json!({"id": 1, "data": {"id": 2}});
+        let path: JsonPath = "id".parse().unwrap();
+        path.remove(&mut value);
+        assert_eq!(value, json!({"data": {"id": 2}}));
+    }
+}
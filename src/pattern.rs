@@ -0,0 +1,238 @@
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// A case-insensitive header name matcher.
+///
+/// Plain names match exactly, names containing `*` or `?` are globs and
+/// names wrapped in slashes (`/^cf-/`) are regular expressions.
+#[derive(Debug, Clone)]
+pub struct HeaderPattern {
+    raw: String,
+    matcher: Matcher,
+}
+
+/// A regular expression that keeps its source text for serialization.
+#[derive(Debug, Clone)]
+pub struct RegexPattern(Regex);
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl HeaderPattern {
+    pub fn matches(&self, name: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(s) => s.eq_ignore_ascii_case(name),
+            Matcher::Regex(re) => re.is_match(name),
+        }
+    }
+
+    /// The header name a plain pattern matches, `None` for globs and regexes.
+    pub(crate) fn exact_name(&self) -> Option<&str> {
+        match &self.matcher {
+            Matcher::Exact(s) => Some(s),
+            Matcher::Regex(_) => None,
+        }
+    }
+}
+
+impl RegexPattern {
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+/// Match `name` against a glob, `*` is any run of characters and `?` is one.
+pub fn glob_match(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut g, mut n) = (0, 0);
+    // position of the last `*` and the name position it currently covers up to
+    let mut star = None;
+
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, n));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sg, sn)) => {
+                    g = sg + 1;
+                    n = sn + 1;
+                    star = Some((sg, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+impl FromStr for HeaderPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = if s.len() > 1 && s.starts_with('/') && s.ends_with('/') {
+            Some(s[1..s.len() - 1].to_string())
+        } else if s.contains(['*', '?']) {
+            Some(glob_to_regex(s))
+        } else {
+            None
+        };
+
+        let matcher = match re {
+            Some(re) => Matcher::Regex(RegexBuilder::new(&re).case_insensitive(true).build()?),
+            None => Matcher::Exact(s.to_string()),
+        };
+
+        Ok(Self {
+            raw: s.to_string(),
+            matcher,
+        })
+    }
+}
+
+impl PartialEq for HeaderPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl Eq for HeaderPattern {}
+
+impl fmt::Display for HeaderPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Serialize for HeaderPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for HeaderPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for HeaderPattern {
+    fn schema_name() -> Cow<'static, str> {
+        "HeaderPattern".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "header name, glob such as `x-ratelimit-*` or regex such as `/^cf-/`"
+        })
+    }
+}
+
+impl FromStr for RegexPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Regex::new(s)?))
+    }
+}
+
+impl PartialEq for RegexPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for RegexPattern {}
+
+impl Serialize for RegexPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RegexPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for RegexPattern {
+    fn schema_name() -> Cow<'static, str> {
+        "RegexPattern".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "format": "regex"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        pattern.parse::<HeaderPattern>().unwrap().matches(name)
+    }
+
+    #[test]
+    fn header_pattern_should_match_case_insensitively() {
+        assert!(matches("Date", "date"));
+        assert!(!matches("date", "x-date"));
+
+        assert!(matches("x-ratelimit-*", "X-RateLimit-Reset"));
+        assert!(matches("x-amzn-?", "x-amzn-a"));
+        assert!(!matches("x-amzn-?", "x-amzn-ab"));
+        assert!(!matches("cf-*", "x-cf-ray"));
+
+        assert!(matches("/^cf-/", "CF-Ray"));
+        assert!(!matches("/^cf-/", "x-cf-ray"));
+    }
+
+    #[test]
+    fn glob_match_should_support_wildcards() {
+        assert!(glob_match("todo", "todo"));
+        assert!(glob_match("todo-*", "todo-new"));
+        assert!(glob_match("*-v?", "users-v2"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("todo-*", "todo"));
+        assert!(!glob_match("Todo", "todo"));
+    }
+
+    #[test]
+    fn header_pattern_should_reject_invalid_regex() {
+        assert!("/(/".parse::<HeaderPattern>().is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use crate::cli::{DiffPart, ExitStatus};
+use crate::{DiffResult, FilteredResponse, JsonChange, ValuePath};
+use anyhow::Result;
+use console::Style;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+/// Machine readable outcome of a run, one entry per profile.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Report {
+    pub profiles: Vec<ProfileReport>,
+    pub summary: Summary,
+}
+
+/// Pass/fail verdict of every profile in a run, in the order they ran.
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub errors: usize,
+    pub profiles: Vec<SummaryRow>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct SummaryRow {
+    pub name: String,
+    pub verdict: Verdict,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Pass,
+    Fail,
+    Error,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileReport {
+    pub name: String,
+    pub identical: bool,
+    pub req1: ResponseSummary,
+    pub req2: ResponseSummary,
+    pub headers: Vec<HeaderChange>,
+    pub body: Vec<JsonChange>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ResponseSummary {
+    pub url: String,
+    pub status: u16,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct HeaderChange {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+impl Summary {
+    pub fn push_result(&mut self, name: &str, result: &DiffResult, fail_on: &[DiffPart]) {
+        let verdict = if result.has_changes(fail_on) {
+            Verdict::Fail
+        } else {
+            Verdict::Pass
+        };
+        self.push(name, verdict, None);
+    }
+
+    pub fn push_error(&mut self, name: &str, error: &anyhow::Error) {
+        self.push(name, Verdict::Error, Some(format!("{:#}", error)));
+    }
+
+    fn push(&mut self, name: &str, verdict: Verdict, error: Option<String>) {
+        self.total += 1;
+        match verdict {
+            Verdict::Pass => self.passed += 1,
+            Verdict::Fail => self.failed += 1,
+            Verdict::Error => self.errors += 1,
+        }
+        self.profiles.push(SummaryRow {
+            name: name.to_string(),
+            verdict,
+            error,
+        });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SummaryRow> {
+        self.profiles.iter().find(|row| row.name == name)
+    }
+
+    /// Errors take precedence over differences.
+    pub fn exit_status(&self) -> ExitStatus {
+        if self.errors > 0 {
+            ExitStatus::Error
+        } else if self.failed > 0 {
+            ExitStatus::DiffFound
+        } else {
+            ExitStatus::Success
+        }
+    }
+
+    pub fn to_text(&self) -> Result<String> {
+        let mut output = String::new();
+        let width = self
+            .profiles
+            .iter()
+            .map(|row| row.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("profile".len());
+
+        writeln!(&mut output, "{:<width$}  result", "profile")?;
+        for row in &self.profiles {
+            let (label, s) = match row.verdict {
+                Verdict::Pass => ("PASS", Style::new().green()),
+                Verdict::Fail => ("FAIL", Style::new().red()),
+                Verdict::Error => ("ERROR", Style::new().red().bold()),
+            };
+            write!(&mut output, "{:<width$}  {}", row.name, s.apply_to(label))?;
+            if let Some(error) = &row.error {
+                write!(&mut output, "  {}", error)?;
+            }
+            writeln!(&mut output)?;
+        }
+        writeln!(
+            &mut output,
+            "\n{} profiles: {} passed, {} failed, {} errors",
+            self.total, self.passed, self.failed, self.errors
+        )?;
+        Ok(output)
+    }
+}
+
+impl ProfileReport {
+    pub fn new(name: &str, result: &DiffResult) -> Self {
+        let body = match &result.changes {
+            Some(changes) => changes.clone(),
+            // non-json bodies can only be reported as a whole
+            None if result.body_changed() => vec![JsonChange::Changed {
+                path: ValuePath::default(),
+                old: result.res1.body.to_value(),
+                new: result.res2.body.to_value(),
+            }],
+            None => vec![],
+        };
+
+        Self {
+            name: name.to_string(),
+            identical: !result.has_changes(&[]),
+            req1: ResponseSummary::new(&result.res1),
+            req2: ResponseSummary::new(&result.res2),
+            headers: diff_headers(&result.res1.headers, &result.res2.headers),
+            body,
+        }
+    }
+}
+
+impl ResponseSummary {
+    fn new(res: &FilteredResponse) -> Self {
+        Self {
+            url: res.url.to_string(),
+            status: res.status.as_u16(),
+            elapsed_ms: res.elapsed.as_millis() as u64,
+        }
+    }
+}
+
+fn diff_headers(headers1: &HeaderMap, headers2: &HeaderMap) -> Vec<HeaderChange> {
+    let names: BTreeSet<&str> = headers1
+        .keys()
+        .chain(headers2.keys())
+        .map(|k| k.as_str())
+        .collect();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old = header_value(headers1, name);
+            let new = header_value(headers2, name);
+            (old != new).then(|| HeaderChange {
+                name: name.to_string(),
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<_> = headers
+        .get_all(name)
+        .iter()
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).to_string())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResponseBody;
+    use reqwest::header::HeaderValue;
+    use reqwest::{StatusCode, Version};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn response(headers: &[(&'static str, &'static str)], body: ResponseBody) -> FilteredResponse {
+        let mut map = HeaderMap::new();
+        for (k, v) in headers {
+            map.append(*k, HeaderValue::from_static(v));
+        }
+        FilteredResponse {
+            url: "http://localhost/todo".parse().unwrap(),
+            version: Version::HTTP_11,
+            status: StatusCode::OK,
+            headers: map,
+            body,
+            elapsed: Duration::from_millis(12),
+        }
+    }
+
+    #[test]
+    fn profile_report_should_list_header_and_body_changes() {
+        let result = DiffResult {
+            res1: response(
+                &[("age", "1"), ("server", "a")],
+                ResponseBody::Text("a".into()),
+            ),
+            res2: response(
+                &[("age", "2"), ("via", "b")],
+                ResponseBody::Text("b".into()),
+            ),
+            changes: None,
+        };
+
+        let report = serde_json::to_value(ProfileReport::new("todo", &result)).unwrap();
+        assert_eq!(
+            report,
+            json!({
+                "name": "todo",
+                "identical": false,
+                "req1": {"url": "http://localhost/todo", "status": 200, "elapsed_ms": 12},
+                "req2": {"url": "http://localhost/todo", "status": 200, "elapsed_ms": 12},
+                "headers": [
+                    {"name": "age", "old": "1", "new": "2"},
+                    {"name": "server", "old": "a"},
+                    {"name": "via", "new": "b"},
+                ],
+                "body": [{"op": "changed", "path": "$", "old": "a", "new": "b"}],
+            })
+        );
+    }
+
+    #[test]
+    fn summary_should_count_verdicts() {
+        let result = DiffResult {
+            res1: response(&[("age", "1")], ResponseBody::Text("a".into())),
+            res2: response(&[("age", "2")], ResponseBody::Text("a".into())),
+            changes: None,
+        };
+
+        let mut summary = Summary::default();
+        summary.push_result("headers", &result, &[]);
+        summary.push_result("body-only", &result, &[DiffPart::Body]);
+        assert_eq!(summary.exit_status(), ExitStatus::DiffFound);
+
+        summary.push_error("broken", &anyhow::anyhow!("connection refused"));
+        assert_eq!(summary.exit_status(), ExitStatus::Error);
+
+        assert_eq!(
+            console::strip_ansi_codes(&summary.to_text().unwrap()),
+            "profile    result\n\
+             headers    FAIL\n\
+             body-only  PASS\n\
+             broken     ERROR  connection refused\n\
+             \n3 profiles: 1 passed, 1 failed, 1 errors\n"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use crate::config::{uri_encode, SECTIONS};
+use anyhow::{anyhow, Context, Error, Result};
+use serde_yaml::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Values for `{{ name }}` placeholders in a config.
+///
+/// A placeholder is looked up in `--var` overrides, then `--env-file`
+/// entries, then the `vars:` section of the config itself and finally the
+/// process environment. Placeholders without a value are an error, `{{{{`
+/// stands for a literal `{{`.
+///
+/// `vars` is a reserved top level key of a config, it can't name a profile.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateVars {
+    overrides: BTreeMap<String, String>,
+}
+
+impl TemplateVars {
+    /// Read `env_files` in order and apply `vars` on top, later values win.
+    pub async fn load(vars: &[(String, String)], env_files: &[String]) -> Result<Self> {
+        let mut overrides = BTreeMap::new();
+        for path in env_files {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("failed to read env file {}", path))?;
+            let entries =
+                parse_env_file(&content).with_context(|| format!("in env file {}", path))?;
+            overrides.extend(entries);
+        }
+        overrides.extend(vars.iter().cloned());
+        Ok(Self { overrides })
+    }
+
+    /// Resolve placeholders in every string of a raw config, except inside
+    /// the `vars:` section whose values are turned into strings.
+    ///
+    /// A profile that fails to render is taken out of the config and returned
+    /// with its error, so the other profiles can still be used. Errors in the
+    /// sections fail the whole config.
+    ///
+    /// Placeholders of values the profile's own `setup` steps `extract` are
+    /// left for `render_extracted` to fill in when requests are sent, as are
+    /// the ones of values any profile extracts when `sequence` is set. Each
+    /// request, a mapping with a `url` or an `env`, lists the names it left
+    /// under `deferred`.
+    pub(crate) fn render_config(
+        &self,
+        config: &mut Value,
+        sequence: bool,
+    ) -> Result<BTreeMap<String, Error>> {
+        let mut failures = BTreeMap::new();
+        let Value::Mapping(map) = config else {
+            return Ok(failures);
+        };
+        let shared = match sequence {
+            true => extracted_names(
+                map.iter()
+                    .filter(|(key, _)| !SECTIONS.contains(&key.as_str().unwrap_or_default()))
+                    .map(|(_, profile)| profile),
+            ),
+            false => BTreeSet::new(),
+        };
+
+        let mut vars = BTreeMap::new();
+        if let Some(Value::Mapping(section)) = map.get_mut("vars") {
+            for (key, value) in section.iter_mut() {
+                let (Some(key), Some(text)) = (key.as_str(), scalar_text(value)) else {
+                    return Err(anyhow!(
+                        "vars.{:?} must be a string, number or bool, \
+                         `vars` holds template variables and can't name a profile",
+                        key
+                    ));
+                };
+                *value = Value::String(text.clone());
+                vars.insert(key.to_string(), text);
+            }
+        }
+
+        for (key, value) in map.iter_mut() {
+            let key = key.as_str().unwrap_or_default();
+            if key == "vars" {
+                continue;
+            }
+            let mut extracted = extracted_names(setup_steps(value));
+            extracted.extend(shared.iter().cloned());
+            let mut deferred = BTreeSet::new();
+            let rendered = self.render_value(value, &vars, &extracted, &mut deferred, key);
+            match rendered {
+                Err(e) if !SECTIONS.contains(&key) => {
+                    failures.insert(key.to_string(), e);
+                }
+                rendered => rendered?,
+            }
+        }
+        for name in failures.keys() {
+            map.remove(name.as_str());
+        }
+        Ok(failures)
+    }
+
+    fn render_value(
+        &self,
+        value: &mut Value,
+        vars: &BTreeMap<String, String>,
+        extracted: &BTreeSet<String>,
+        deferred: &mut BTreeSet<String>,
+        path: &str,
+    ) -> Result<()> {
+        match value {
+            Value::String(s) => {
+                *s = render_text(s, |name| match extracted.contains(name) {
+                    true => {
+                        deferred.insert(name.to_string());
+                        Ok(format!("{{{{ {} }}}}", name))
+                    }
+                    false => self.lookup(name, vars),
+                })
+                .with_context(|| format!("failed to render {}", path))?
+            }
+            Value::Sequence(seq) => {
+                for (i, v) in seq.iter_mut().enumerate() {
+                    let path = format!("{}[{}]", path, i);
+                    self.render_value(v, vars, extracted, deferred, &path)?;
+                }
+            }
+            Value::Mapping(map) => {
+                let request = map.contains_key("url") || map.contains_key("env");
+                let mut own = BTreeSet::new();
+                let names = match request {
+                    true => &mut own,
+                    false => deferred,
+                };
+                for (k, v) in map.iter_mut() {
+                    let path = format!("{}.{}", path, k.as_str().unwrap_or_default());
+                    self.render_value(v, vars, extracted, names, &path)?;
+                }
+                if !own.is_empty() {
+                    map.insert(
+                        "deferred".into(),
+                        own.into_iter().map(Value::from).collect(),
+                    );
+                }
+            }
+            Value::Tagged(tagged) => {
+                self.render_value(&mut tagged.value, vars, extracted, deferred, path)?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+        self.overrides
+            .get(name)
+            .or_else(|| vars.get(name))
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+            .ok_or_else(|| anyhow!("undefined variable {}", name))
+    }
+}
+
+impl FromIterator<(String, String)> for TemplateVars {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        Self {
+            overrides: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Fill the `{{ name }}` placeholders `render_config` left for values
+/// extracted from earlier responses. Placeholders of names without a value
+/// and any other text are kept as they are.
+pub(crate) fn render_extracted(text: &str, extracted: &BTreeMap<String, String>) -> String {
+    fill_placeholders(text, &["{{"], &["}}"], |name| {
+        extracted.get(name.trim()).cloned()
+    })
+}
+
+/// `render_extracted` for a url, where `Url` may have percent-encoded the
+/// placeholders. Values are percent-encoded so they stay within the part of
+/// the url they are put in.
+pub(crate) fn render_extracted_url(url: &str, extracted: &BTreeMap<String, String>) -> String {
+    fill_placeholders(url, &["{{", "%7B%7B"], &["}}", "%7D%7D"], |name| {
+        let value = extracted.get(percent_decode(name)?.trim())?;
+        Some(uri_encode(value, true))
+    })
+}
+
+/// Replace the text between any of `open` and the next of `close` with what
+/// `lookup` gives for it, leaving it alone when that's nothing.
+fn fill_placeholders(
+    text: &str,
+    open: &[&str],
+    close: &[&str],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> String {
+    let find = |text: &str, tokens: &[&str]| {
+        tokens
+            .iter()
+            .filter_map(|token| Some((text.find(token)?, token.len())))
+            .min()
+    };
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((start, len)) = find(rest, open) {
+        output.push_str(&rest[..start]);
+        let inner = &rest[start + len..];
+        let filled =
+            find(inner, close).and_then(|(end, len)| Some((lookup(&inner[..end])?, end + len)));
+        match filled {
+            Some((value, skip)) => {
+                output.push_str(&value);
+                rest = &inner[skip..];
+            }
+            None => {
+                output.push_str(&rest[start..start + len]);
+                rest = inner;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Replace every `{{ name }}` of `text` with what `lookup` gives for it and
+/// every `{{{{` with `{{`.
+fn render_text(text: &str, mut lookup: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        if rest[start..].starts_with("{{{{") {
+            output.push_str("{{");
+            rest = &rest[start + 4..];
+            continue;
+        }
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| anyhow!("unclosed {{{{ in {:?}", text))?;
+        let name = rest[start + 2..start + end].trim();
+        output.push_str(&lookup(name)?);
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Names of the values the `extract` rules of `requests` give.
+fn extracted_names<'a>(requests: impl IntoIterator<Item = &'a Value>) -> BTreeSet<String> {
+    requests
+        .into_iter()
+        .filter_map(|request| request.get("extract")?.as_mapping())
+        .flat_map(|extract| extract.keys().filter_map(Value::as_str).map(String::from))
+        .collect()
+}
+
+/// Setup steps of a profile, whether both sides share them or not.
+fn setup_steps(profile: &Value) -> impl Iterator<Item = &Value> {
+    let setup = profile.get("setup");
+    let sides = ["req1", "req2"].map(|side| setup.and_then(|setup| setup.get(side)));
+    [setup]
+        .into_iter()
+        .chain(sides)
+        .flatten()
+        .filter_map(Value::as_sequence)
+        .flatten()
+}
+
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Parse a dotenv file: `KEY=value` lines, `#` comments, an optional
+/// `export ` prefix and values optionally wrapped in matching quotes.
+pub fn parse_env_file(content: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected KEY=value", i + 1))?;
+        let value = value.trim();
+        let value = match value.as_bytes() {
+            [q @ (b'"' | b'\''), .., last] if q == last => &value[1..value.len() - 1],
+            _ => value,
+        };
+        entries.push((key.trim().to_string(), value.to_string()));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_config_should_resolve_vars_by_precedence() {
+        let mut config: Value = serde_yaml::from_str(
+            r#"
+vars:
+  host: localhost
+  port: 8080
+  PATH: todo
+todo:
+  req1:
+    url: "http://{{ host }}:{{port}}/{{ PATH }}"
+    headers:
+      authorization: "Bearer {{ token }}"
+    body:
+      template: "{{{{ name }}, {{{{{{ host }}"
+"#,
+        )
+        .unwrap();
+        let vars: TemplateVars = [("token".to_string(), "abc".to_string())]
+            .into_iter()
+            .collect();
+        assert!(vars.render_config(&mut config, false).unwrap().is_empty());
+
+        assert_eq!(config["vars"]["port"], Value::from("8080"));
+        assert_eq!(
+            config["todo"]["req1"]["url"],
+            Value::from("http://localhost:8080/todo")
+        );
+        assert_eq!(
+            config["todo"]["req1"]["headers"]["authorization"],
+            Value::from("Bearer abc")
+        );
+        assert_eq!(
+            config["todo"]["req1"]["body"]["template"],
+            Value::from("{{ name }}, {{localhost")
+        );
+    }
+
+    #[test]
+    fn render_config_should_leave_extracted_values_for_later() {
+        let mut config: Value = serde_yaml::from_str(
+            r#"
+todo:
+  setup:
+    - url: "http://localhost/login"
+      extract:
+        token: access_token
+  req1:
+    url: "http://localhost/todo"
+    headers:
+      authorization: "Bearer {{token}}"
+login:
+  url: "http://localhost/login"
+  extract:
+    token: access_token
+other:
+  url: "http://localhost/todo/{{ token }}"
+"#,
+        )
+        .unwrap();
+        let mut sequence = config.clone();
+        let failures = TemplateVars::default()
+            .render_config(&mut config, false)
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", failures["other"]),
+            "failed to render other.url: undefined variable token"
+        );
+        let failures = TemplateVars::default()
+            .render_config(&mut sequence, true)
+            .unwrap();
+        assert!(failures.is_empty());
+        assert_eq!(
+            sequence["other"]["url"],
+            Value::from("http://localhost/todo/{{ token }}")
+        );
+        let authorization = &config["todo"]["req1"]["headers"]["authorization"];
+        assert_eq!(authorization, &Value::from("Bearer {{ token }}"));
+
+        let extracted = [("token".to_string(), "a b/c".to_string())].into();
+        let authorization = authorization.as_str().unwrap();
+        assert_eq!(render_extracted(authorization, &extracted), "Bearer a b/c");
+        assert_eq!(
+            config["todo"]["req1"]["deferred"],
+            serde_yaml::from_str::<Value>("[token]").unwrap()
+        );
+        assert!(config["todo"]["setup"][0].get("deferred").is_none());
+    }
+
+    #[test]
+    fn render_extracted_url_should_encode_values_and_keep_other_braces() {
+        let extracted = [("token".to_string(), "a b/c&d".to_string())].into();
+        let url = "http://localhost/%7B%7B%20token%20%7D%7D?q={{token}}&raw=%7B%7Bx%7D%7D";
+        assert_eq!(
+            render_extracted_url(url, &extracted),
+            "http://localhost/a%20b%2Fc%26d?q=a%20b%2Fc%26d&raw=%7B%7Bx%7D%7D"
+        );
+    }
+
+    #[test]
+    fn render_config_should_fail_on_undefined_variable() {
+        let mut config: Value =
+            serde_yaml::from_str("todo: {req1: {url: 'http://{{ xdiff_no_such_var }}/'}}").unwrap();
+        let failures = TemplateVars::default()
+            .render_config(&mut config, false)
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", failures["todo"]),
+            "failed to render todo.req1.url: undefined variable xdiff_no_such_var"
+        );
+        assert!(config.get("todo").is_none());
+    }
+
+    #[test]
+    fn parse_env_file_should_work() {
+        let content = "# comment\nHOST=localhost\nexport TOKEN=\"a b\"\n\nEMPTY=\n";
+        assert_eq!(
+            parse_env_file(content).unwrap(),
+            vec![
+                ("HOST".to_string(), "localhost".to_string()),
+                ("TOKEN".to_string(), "a b".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+            ]
+        );
+        assert!(parse_env_file("oops").is_err());
+    }
+}
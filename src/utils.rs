@@ -1,7 +1,8 @@
+use crate::{cli::ExitStatus, diff::JsonChange};
 use anyhow::Result;
-use console::{style, Style};
+use console::{measure_text_width, style, Style};
 use core::fmt;
-use similar::{ChangeTag, TextDiff};
+use similar::{ChangeTag, InlineChange, TextDiff};
 
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
@@ -61,6 +62,119 @@ pub fn diff_text(text1: &str, text2: &str) -> Result<String> {
     Ok(output)
 }
 
+/// Render the diff in two columns that share the given terminal width.
+///
+/// Long lines wrap within their column instead of being truncated.
+pub fn diff_text_side_by_side(text1: &str, text2: &str, width: usize) -> Result<String> {
+    // line number, `|` and sign on each side plus the ` │ ` separator
+    let column = (width.saturating_sub(2 * 6 + 3) / 2).max(10);
+    let mut output = String::new();
+    let diff = TextDiff::from_lines(text1, text2);
+
+    for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+        if idx > 0 {
+            writeln!(&mut output, "{:-^1$}", "-", 2 * (column + 6) + 3)?
+        }
+
+        for op in group {
+            let mut left = vec![];
+            let mut right = vec![];
+            for change in diff.iter_inline_changes(op) {
+                match change.tag() {
+                    ChangeTag::Delete => left.push(side_rows(&change, change.old_index(), column)),
+                    ChangeTag::Insert => right.push(side_rows(&change, change.new_index(), column)),
+                    ChangeTag::Equal => {
+                        left.push(side_rows(&change, change.old_index(), column));
+                        right.push(side_rows(&change, change.new_index(), column));
+                    }
+                }
+            }
+
+            let blank = " ".repeat(column + 6);
+            for idx in 0..left.len().max(right.len()) {
+                let l = left.get(idx).map(Vec::as_slice).unwrap_or_default();
+                let r = right.get(idx).map(Vec::as_slice).unwrap_or_default();
+                for row in 0..l.len().max(r.len()) {
+                    writeln!(
+                        &mut output,
+                        "{} {} {}",
+                        l.get(row).unwrap_or(&blank),
+                        style("│").dim(),
+                        r.get(row).map(|s| s.trim_end()).unwrap_or_default(),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Wrap one side of a change into rows exactly `column` wide after the gutter.
+fn side_rows(change: &InlineChange<str>, line: Option<usize>, column: usize) -> Vec<String> {
+    let (sign, s) = match change.tag() {
+        ChangeTag::Delete => ("-", Style::new().red()),
+        ChangeTag::Insert => ("+", Style::new().green()),
+        ChangeTag::Equal => (" ", Style::new().dim()),
+    };
+
+    let mut rows = vec![];
+    let mut row = String::new();
+    let mut width = 0;
+    for (emphasized, value) in change.iter_strings_lossy() {
+        let s = if emphasized {
+            s.clone().underlined().on_black()
+        } else {
+            s.clone()
+        };
+        let mut run = String::new();
+        for c in value.trim_end_matches(['\r', '\n']).chars() {
+            let w = measure_text_width(c.encode_utf8(&mut [0; 4]));
+            if width + w > column {
+                row.push_str(&s.apply_to(&run).to_string());
+                rows.push(format!("{}{}", row, " ".repeat(column - width)));
+                run.clear();
+                row.clear();
+                width = 0;
+            }
+            run.push(c);
+            width += w;
+        }
+        row.push_str(&s.apply_to(&run).to_string());
+    }
+    rows.push(format!(
+        "{}{}",
+        row,
+        " ".repeat(column.saturating_sub(width))
+    ));
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(idx, row)| match idx {
+            0 => format!(
+                "{}|{}{}",
+                style(Line(line)).dim(),
+                s.apply_to(sign).bold(),
+                row
+            ),
+            _ => format!("{}| {}", Line(None), row),
+        })
+        .collect()
+}
+
+pub fn diff_json_text(changes: &[JsonChange]) -> Result<String> {
+    let mut output = String::new();
+
+    for change in changes {
+        let s = match change {
+            JsonChange::Removed { .. } => Style::new().red(),
+            JsonChange::Added { .. } => Style::new().green(),
+            JsonChange::Changed { .. } => Style::new().yellow(),
+        };
+        writeln!(&mut output, "{}", s.apply_to(change))?;
+    }
+    Ok(output)
+}
+
 pub fn highlight_text(text: &str, extension: &str, theme: Option<&str>) -> Result<String> {
     // Load these once at the start of your program
     let ps = SyntaxSet::load_defaults_newlines();
@@ -84,15 +198,50 @@ pub fn highlight_text(text: &str, extension: &str, theme: Option<&str>) -> Resul
     Ok(output)
 }
 
-pub fn process_error_output(result: Result<()>) -> Result<()> {
+/// Split comma separated tags as typed into the parse wizard.
+pub fn split_tags(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub fn process_error_output(result: Result<ExitStatus>) -> ExitStatus {
     match result {
-        Ok(_) => {}
+        Ok(status) => status,
         Err(e) => {
             let stderr = std::io::stderr();
             let mut stderr = stderr.lock();
             let s = Style::new().red();
-            write!(stderr, "{}", s.apply_to(format!("{:?}", e)));
+            // nothing sensible is left to do if stderr is gone
+            let _ = writeln!(stderr, "{}", s.apply_to(format!("{:?}", e)));
+            ExitStatus::Error
         }
-    };
-    Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_text_side_by_side_should_wrap_long_lines() {
+        let text1 = "same\nold line\n";
+        let text2 = "same\nthe new line is much longer than a column\n";
+
+        let output = diff_text_side_by_side(text1, text2, 55).unwrap();
+        let output = console::strip_ansi_codes(&output);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "1   | same                 │ 1   | same",
+                "2   |-old line             │ 2   |+the new line is much",
+                "                           │     |  longer than a colum",
+                "                           │     | n",
+            ]
+        );
+    }
 }